@@ -0,0 +1,196 @@
+use crate::data_source::asset::AssetData;
+use crate::secp256k1::Point;
+use crate::serialization::Serialize;
+use num_bigint::BigUint;
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use std::collections::{BTreeMap, HashSet};
+
+/// A key controlled by the prover along with its balance. The private key stays with the `Signer`.
+pub type OwnedKey = (Point, BigUint);
+
+/// A public key from the chain along with its balance
+pub type CandidateKey = (Point, BigUint);
+
+/// Builds the set of public keys the asset proofs are published over.
+///
+/// The asset proof only hides which keys the prover owns when they are mixed in with keys it does
+/// not own. Decoys are chosen from a pool of candidate keys so that their balances follow the same
+/// distribution as the owned keys, measured by the bit length of the balance. When a balance range
+/// runs out of candidates the remainder is drawn from the rest of the pool at random.
+pub struct AnonymitySetBuilder {
+    owned: Vec<OwnedKey>,
+    candidates: Vec<CandidateKey>,
+    decoys: usize,
+}
+
+impl AnonymitySetBuilder {
+    pub fn new(owned: Vec<OwnedKey>, candidates: Vec<CandidateKey>) -> AnonymitySetBuilder {
+        AnonymitySetBuilder {
+            decoys: owned.len(),
+            owned,
+            candidates,
+        }
+    }
+
+    /// Number of decoy keys to mix in with the owned keys. Defaults to one per owned key.
+    pub fn decoys(mut self, decoys: usize) -> AnonymitySetBuilder {
+        self.decoys = decoys;
+        self
+    }
+
+    /// Select the decoys and shuffle them together with the owned keys.
+    pub fn build(self) -> Result<Vec<AssetData>, &'static str> {
+        let mut rng = OsRng::new().expect("OsRng");
+        let decoys = self.select_decoys(&mut rng)?;
+
+        let mut set: Vec<AssetData> = self.owned.into_iter().chain(decoys).collect();
+        set.shuffle(&mut rng);
+
+        Ok(set)
+    }
+
+    fn select_decoys(&self, rng: &mut OsRng) -> Result<Vec<CandidateKey>, &'static str> {
+        let mut buckets = self.candidate_buckets();
+        let available: usize = buckets.values().map(|bucket| bucket.len()).sum();
+        if available < self.decoys {
+            return Err("not enough candidate keys for the requested decoys");
+        }
+
+        for bucket in buckets.values_mut() {
+            bucket.shuffle(rng);
+        }
+
+        let mut decoys = Vec::with_capacity(self.decoys);
+        for (bits, quota) in self.quotas() {
+            if let Some(bucket) = buckets.get_mut(&bits) {
+                let take = quota.min(bucket.len());
+                decoys.extend(bucket.drain(..take));
+            }
+        }
+
+        let mut remaining: Vec<CandidateKey> = buckets.into_iter().flat_map(|b| b.1).collect();
+        remaining.shuffle(rng);
+        let shortfall = self.decoys - decoys.len();
+        decoys.extend(remaining.into_iter().take(shortfall));
+
+        Ok(decoys)
+    }
+
+    /// Candidates grouped by the bit length of their balance, excluding owned keys and duplicates.
+    fn candidate_buckets(&self) -> BTreeMap<usize, Vec<CandidateKey>> {
+        let mut seen: HashSet<Vec<u8>> = self.owned.iter().map(|key| key.0.serialize()).collect();
+        let mut buckets: BTreeMap<usize, Vec<CandidateKey>> = BTreeMap::new();
+
+        for candidate in self.candidates.iter() {
+            if seen.insert(candidate.0.serialize()) {
+                buckets
+                    .entry(candidate.1.bits())
+                    .or_insert_with(Vec::new)
+                    .push(candidate.clone());
+            }
+        }
+
+        buckets
+    }
+
+    /// Number of decoys to draw for each balance bit length, proportional to how many owned keys
+    /// have a balance of that bit length.
+    fn quotas(&self) -> Vec<(usize, usize)> {
+        let mut counts: BTreeMap<usize, usize> = BTreeMap::new();
+        for key in self.owned.iter() {
+            *counts.entry(key.1.bits()).or_insert(0) += 1;
+        }
+
+        let owned = self.owned.len();
+        let mut quotas: Vec<(usize, usize)> = counts
+            .iter()
+            .map(|(bits, count)| (*bits, self.decoys * count / owned))
+            .collect();
+
+        // Hand out what was lost to rounding, most common balance ranges first
+        let mut by_count: Vec<(usize, usize)> = counts.into_iter().collect();
+        by_count.sort_by(|a, b| b.1.cmp(&a.1));
+        let assigned: usize = quotas.iter().map(|quota| quota.1).sum();
+        for (bits, _) in by_count.into_iter().take(self.decoys - assigned) {
+            if let Some(quota) = quotas.iter_mut().find(|quota| quota.0 == bits) {
+                quota.1 += 1;
+            }
+        }
+
+        quotas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fields::Field256;
+    use crate::secp256k1::point_mul;
+
+    #[test]
+    fn anonymity_set_mixes_owned_keys_with_decoys() {
+        let owned = gen_owned(&[10, 12]);
+        let candidates = gen_candidates(100, &[10, 11, 12, 13, 14, 15]);
+
+        let set = AnonymitySetBuilder::new(owned.clone(), candidates)
+            .decoys(4)
+            .build()
+            .unwrap();
+
+        assert_eq!(set.len(), 6);
+        for key in owned.iter() {
+            assert!(set.iter().any(|asset| asset.0 == key.0));
+        }
+        let unique: HashSet<Vec<u8>> = set.iter().map(|asset| asset.0.serialize()).collect();
+        assert_eq!(unique.len(), set.len());
+    }
+
+    #[test]
+    fn anonymity_set_decoys_match_balance_distribution() {
+        let owned = gen_owned(&[10, 12]);
+        let mut candidates = gen_candidates(100, &[9, 10, 11, 12, 13]);
+        candidates.extend(gen_candidates(200, &[1_000_000, 2_000_000, 3_000_000]));
+
+        let set = AnonymitySetBuilder::new(owned, candidates)
+            .decoys(4)
+            .build()
+            .unwrap();
+
+        assert!(set.iter().all(|asset| asset.1 < BigUint::from(16u8)));
+    }
+
+    #[test]
+    fn anonymity_set_requires_enough_candidates() {
+        let owned = gen_owned(&[10]);
+        let candidates = gen_candidates(100, &[10, 11]);
+
+        let result = AnonymitySetBuilder::new(owned, candidates)
+            .decoys(3)
+            .build();
+
+        assert!(result.is_err());
+    }
+
+    fn gen_owned(balances: &[u32]) -> Vec<OwnedKey> {
+        balances
+            .iter()
+            .enumerate()
+            .map(|(i, bal)| {
+                let y = point_mul(Point::g(), &Field256::from(i as i32 + 1));
+                (y, BigUint::from(*bal))
+            })
+            .collect()
+    }
+
+    fn gen_candidates(first_sk: i32, balances: &[u32]) -> Vec<CandidateKey> {
+        balances
+            .iter()
+            .enumerate()
+            .map(|(i, bal)| {
+                let y = point_mul(Point::g(), &Field256::from(first_sk + i as i32));
+                (y, BigUint::from(*bal))
+            })
+            .collect()
+    }
+}
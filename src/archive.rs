@@ -0,0 +1,455 @@
+use crate::epoch::{Epoch, EpochProof};
+use crate::params::Params;
+use crate::proofs::{AssetProof, CommitmentPath, LiabilityProof, SolvencyBundle};
+use crate::serialization::{Deserialize, Serialize};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const BUNDLE_FILE: &str = "bundle";
+const ASSETS_FILE: &str = "assets";
+const LIABILITIES_FILE: &str = "liabilities";
+
+/// Directory of published proofs keyed by epoch.
+///
+/// Every epoch is kept in its own directory named after the zero padded epoch id, holding the
+/// solvency bundle and optionally the asset and liability proofs it was computed from, each
+/// tagged with the epoch. Bundles can only be added in epoch order so the archive always
+/// describes a consistent history.
+pub struct EpochArchive {
+    path: PathBuf,
+}
+
+impl EpochArchive {
+    /// Open the archive at `path`, creating the directory if needed
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<EpochArchive> {
+        fs::create_dir_all(&path)?;
+        Ok(EpochArchive {
+            path: path.as_ref().to_path_buf(),
+        })
+    }
+
+    /// Epochs with a stored bundle, oldest first
+    pub fn epochs(&self) -> io::Result<Vec<Epoch>> {
+        let mut ids = vec![];
+        for entry in fs::read_dir(&self.path)? {
+            let name = entry?.file_name();
+            if let Some(id) = name.to_str().and_then(|name| name.parse::<u64>().ok()) {
+                ids.push(id);
+            }
+        }
+        ids.sort();
+
+        ids.into_iter()
+            .map(|id| self.bundle(id).map(|bundle| bundle.epoch))
+            .collect()
+    }
+
+    /// The most recently stored epoch, if any
+    pub fn latest(&self) -> io::Result<Option<Epoch>> {
+        Ok(self.epochs()?.pop())
+    }
+
+    /// Store the bundle under its epoch, which must follow the latest stored epoch
+    pub fn put_bundle(&self, bundle: &SolvencyBundle) -> io::Result<()> {
+        if let Some(latest) = self.latest()? {
+            if !latest.precedes(&bundle.epoch) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "epoch does not follow the latest archived epoch",
+                ));
+            }
+        }
+
+        fs::create_dir_all(self.epoch_path(bundle.epoch.id))?;
+        fs::write(self.file(bundle.epoch.id, BUNDLE_FILE), bundle.serialize())
+    }
+
+    pub fn bundle(&self, id: u64) -> io::Result<SolvencyBundle> {
+        let bytes = fs::read(self.file(id, BUNDLE_FILE))?;
+        SolvencyBundle::read(&bytes).map_err(invalid_data)
+    }
+
+    /// Store the public part of the asset proofs of an archived epoch, which must all be tagged
+    /// with its epoch. The secret v, which would reveal which keys are owned, is left out.
+    pub fn put_asset_proofs(&self, id: u64, proofs: &[EpochProof<AssetProof>]) -> io::Result<()> {
+        let path = self.existing_file(id, ASSETS_FILE)?;
+        self.check_epochs(id, proofs)?;
+        let bytes: Vec<u8> = proofs
+            .iter()
+            .flat_map(|tagged| {
+                let mut out = tagged.epoch.serialize();
+                out.extend(tagged.proof.serialize_public());
+                out
+            })
+            .collect();
+        fs::write(path, bytes)
+    }
+
+    /// Asset proofs of an archived epoch, read with the parameters of its bundle. Their secret v
+    /// is zero.
+    pub fn asset_proofs(&self, id: u64) -> io::Result<Vec<EpochProof<AssetProof>>> {
+        let params = self.bundle(id)?.params;
+        let bytes = fs::read(self.file(id, ASSETS_FILE))?;
+        let len = 24 + AssetProof::PUBLIC_ENCODED_LEN;
+        if bytes.len() % len != 0 {
+            return Err(invalid_data("truncated asset proofs"));
+        }
+        let proofs: Vec<EpochProof<AssetProof>> = bytes
+            .chunks(len)
+            .map(|chunk| {
+                let proof = AssetProof::deserialize_public_with(&chunk[24..], &params);
+                EpochProof::new(Epoch::deserialize(&chunk[0..24]), proof)
+            })
+            .collect();
+        self.check_epochs(id, &proofs)?;
+        Ok(proofs)
+    }
+
+    /// Store the public part of the liability proofs of an archived epoch, which must all be
+    /// tagged with its epoch. The customer secrets are left out, so the archive can be published
+    /// as is.
+    pub fn put_liability_proofs(
+        &self,
+        id: u64,
+        proofs: &[EpochProof<LiabilityProof>],
+    ) -> io::Result<()> {
+        let path = self.existing_file(id, LIABILITIES_FILE)?;
+        self.check_epochs(id, proofs)?;
+        let bytes: Vec<u8> = proofs
+            .iter()
+            .flat_map(|tagged| {
+                let mut out = tagged.epoch.serialize();
+                out.extend(tagged.proof.serialize_public());
+                out
+            })
+            .collect();
+        fs::write(path, bytes)
+    }
+
+    /// Public encodings of the liability proofs of an archived epoch, as written by
+    /// `LiabilityProof::serialize_public`, tagged with the epoch
+    pub fn liability_proofs(&self, id: u64) -> io::Result<Vec<EpochProof<Vec<u8>>>> {
+        let params = self.bundle(id)?.params;
+        let bytes = fs::read(self.file(id, LIABILITIES_FILE))?;
+        let len = 24 + LiabilityProof::public_encoded_len(&params);
+        if bytes.len() % len != 0 {
+            return Err(invalid_data("truncated liability proofs"));
+        }
+        let proofs: Vec<EpochProof<Vec<u8>>> = bytes
+            .chunks(len)
+            .map(|chunk| EpochProof::new(Epoch::deserialize(&chunk[0..24]), chunk[24..].to_vec()))
+            .collect();
+        self.check_epochs(id, &proofs)?;
+        Ok(proofs)
+    }
+
+    /// Fail unless every proof is tagged with the epoch of the bundle archived under `id`
+    fn check_epochs<T>(&self, id: u64, proofs: &[EpochProof<T>]) -> io::Result<()> {
+        let epoch = self.bundle(id)?.epoch;
+        if proofs.iter().any(|tagged| tagged.epoch != epoch) {
+            return Err(invalid_data("proof tagged with another epoch"));
+        }
+        Ok(())
+    }
+
+    fn epoch_path(&self, id: u64) -> PathBuf {
+        self.path.join(format!("{:020}", id))
+    }
+
+    fn file(&self, id: u64, name: &str) -> PathBuf {
+        self.epoch_path(id).join(name)
+    }
+
+    /// Path of a file of an epoch whose bundle has already been stored
+    fn existing_file(&self, id: u64, name: &str) -> io::Result<PathBuf> {
+        if !self.file(id, BUNDLE_FILE).exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no bundle archived for epoch",
+            ));
+        }
+        Ok(self.file(id, name))
+    }
+}
+
+/// What a customer keeps from each publication: their own liability proof with its secrets,
+/// tagged with the epoch it was published in, the balance they were told it commits to and their
+/// path in the commitment tree.
+pub struct CustomerReceipt {
+    pub balance: BigUint,
+    pub path: CommitmentPath,
+    pub proof: EpochProof<LiabilityProof>,
+}
+
+impl CustomerReceipt {
+    pub fn epoch(&self) -> &Epoch {
+        &self.proof.epoch
+    }
+
+    /// Encodes into 8 + 4 + path length + 24 + liability proof length bytes. Fails if the
+    /// balance doesn't fit in 8 bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, &'static str> {
+        let balance = self.balance.to_u64().ok_or("balance too large")?;
+        let path = self.path.serialize();
+        let mut out = balance.to_be_bytes().to_vec();
+        out.extend(&(path.len() as u32).to_be_bytes());
+        out.extend(path);
+        out.extend(self.proof.serialize());
+        Ok(out)
+    }
+}
+
+impl CustomerReceipt {
+    /// Read the encoding returned by `to_bytes`, with a liability proof created with `params`
+    pub fn deserialize_with(bytes: &[u8], params: &Params) -> CustomerReceipt {
+        let mut balance = [0; 8];
+        balance.copy_from_slice(&bytes[0..8]);
+        let mut path_len = [0; 4];
+        path_len.copy_from_slice(&bytes[8..12]);
+        let path_end = 12 + u32::from_be_bytes(path_len) as usize;
+
+        CustomerReceipt {
+            balance: BigUint::from(u64::from_be_bytes(balance)),
+            path: CommitmentPath::deserialize(&bytes[12..path_end]),
+            proof: EpochProof::new(
+                Epoch::deserialize(&bytes[path_end..(path_end + 24)]),
+                LiabilityProof::deserialize_with(&bytes[(path_end + 24)..], params),
+            ),
+        }
+    }
+}
+
+impl Deserialize for CustomerReceipt {
+    /// Reads the receipt over the default parameters
+    fn deserialize(bytes: &[u8]) -> CustomerReceipt {
+        CustomerReceipt::deserialize_with(bytes, &Params::default())
+    }
+}
+
+/// A problem found while checking a customer's receipts, identified by epoch id
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReceiptIssue {
+    /// The receipt's epoch doesn't follow the one of the receipt before it
+    OutOfOrder(u64),
+    /// No bundle is archived for the epoch
+    MissingEpoch(u64),
+    /// The archived bundle was published under a different epoch than the receipt
+    EpochMismatch(u64),
+    /// The archived proof of solvency doesn't verify
+    InvalidSolvencyProof(u64),
+    /// The receipt doesn't open to the balance or isn't summed into the archived total
+    BalanceNotIncluded(u64),
+}
+
+/// Check a customer's receipts, oldest first, against the archived bundles and the expected
+/// parameters, rather than the ones recorded in the archive. Returns every issue found, so an
+/// empty result means the customer's balance was included in every epoch.
+pub fn check_receipts(
+    identifier: &[u8],
+    receipts: &[CustomerReceipt],
+    archive: &EpochArchive,
+    params: &Params,
+) -> io::Result<Vec<ReceiptIssue>> {
+    let mut issues = vec![];
+    let mut previous: Option<&Epoch> = None;
+
+    for receipt in receipts.iter() {
+        let id = receipt.epoch().id;
+        if let Some(previous) = previous {
+            if !previous.precedes(receipt.epoch()) {
+                issues.push(ReceiptIssue::OutOfOrder(id));
+            }
+        }
+        previous = Some(receipt.epoch());
+
+        let bundle = match archive.bundle(id) {
+            Ok(bundle) => bundle,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                issues.push(ReceiptIssue::MissingEpoch(id));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        if bundle.epoch != *receipt.epoch() {
+            issues.push(ReceiptIssue::EpochMismatch(id));
+        }
+        if !bundle.params.matches(params) || bundle.solvency.check_with(params).is_err() {
+            issues.push(ReceiptIssue::InvalidSolvencyProof(id));
+        }
+        if receipt.proof.proof.check_with(params).is_err()
+            || !receipt.proof.proof.verify_as_customer_with_path(
+                identifier,
+                &receipt.balance,
+                &receipt.path,
+                &bundle.commitment_root,
+            )
+        {
+            issues.push(ReceiptIssue::BalanceNotIncluded(id));
+        }
+    }
+
+    Ok(issues)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fields::Field256;
+    use crate::proofs::CommitmentTree;
+    use crate::secp256k1::{point_mul, Point};
+
+    #[test]
+    fn archive_checks_receipts_across_epochs() {
+        let dir = temp_dir("receipts");
+        let archive = EpochArchive::open(&dir).unwrap();
+
+        let epochs = [
+            Epoch::new(1, 1_560_000_000, 580_000),
+            Epoch::new(2, 1_560_086_400, 580_144),
+        ];
+        let mut receipts = vec![];
+        for (epoch, balance) in epochs.iter().zip([10u8, 12].iter()) {
+            let (bundle, receipt) = publish(*epoch, BigUint::from(*balance));
+            archive.put_bundle(&bundle).unwrap();
+            receipts.push(receipt);
+        }
+
+        assert_eq!(archive.epochs().unwrap(), epochs.to_vec());
+        let params = Params::default();
+        assert!(check_receipts(b"alice", &receipts, &archive, &params)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            check_receipts(b"alice", &receipts, &archive, &Params::new(b"test")).unwrap(),
+            vec![
+                ReceiptIssue::InvalidSolvencyProof(1),
+                ReceiptIssue::BalanceNotIncluded(1),
+                ReceiptIssue::InvalidSolvencyProof(2),
+                ReceiptIssue::BalanceNotIncluded(2),
+            ]
+        );
+
+        receipts[1].balance = BigUint::from(13u8);
+        let (_, missing) = publish(Epoch::new(3, 1_560_172_800, 580_288), BigUint::from(1u8));
+        receipts.push(missing);
+        assert_eq!(
+            check_receipts(b"alice", &receipts, &archive, &params).unwrap(),
+            vec![
+                ReceiptIssue::BalanceNotIncluded(2),
+                ReceiptIssue::MissingEpoch(3)
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn archive_rejects_out_of_order_epochs() {
+        let dir = temp_dir("order");
+        let archive = EpochArchive::open(&dir).unwrap();
+
+        let (bundle, _) = publish(Epoch::new(2, 1_560_086_400, 580_144), BigUint::from(1u8));
+        archive.put_bundle(&bundle).unwrap();
+        let (bundle, _) = publish(Epoch::new(1, 1_560_000_000, 580_000), BigUint::from(1u8));
+
+        assert!(archive.put_bundle(&bundle).is_err());
+        assert!(archive.put_asset_proofs(1, &[]).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn archive_rejects_proofs_from_other_epochs() {
+        let dir = temp_dir("tags");
+        let archive = EpochArchive::open(&dir).unwrap();
+        let epoch = Epoch::new(1, 1_560_000_000, 580_000);
+        let (bundle, receipt) = publish(epoch, BigUint::from(1u8));
+        archive.put_bundle(&bundle).unwrap();
+
+        let proof = LiabilityProof::deserialize(&receipt.proof.proof.serialize());
+        let tagged = EpochProof::new(epoch, proof);
+        archive.put_liability_proofs(1, &[tagged]).unwrap();
+        let stored = archive.liability_proofs(1).unwrap();
+        assert_eq!(stored[0].epoch, epoch);
+        assert_eq!(stored[0].proof, receipt.proof.proof.serialize_public());
+
+        let other = EpochProof::new(Epoch::new(2, 1_560_086_400, 580_144), receipt.proof.proof);
+        assert!(archive.put_liability_proofs(1, &[other]).is_err());
+
+        // The secret v of asset proofs isn't archived
+        let (g, h) = (crate::g(), crate::h());
+        let y = point_mul(Point::g(), &Field256::from(2));
+        let proof = AssetProof::create(None, &y, BigUint::from(3u8), &g, &h);
+        let public = proof.serialize_public();
+        archive
+            .put_asset_proofs(1, &[EpochProof::new(epoch, proof)])
+            .unwrap();
+        let stored = archive.asset_proofs(1).unwrap();
+        assert_eq!(stored[0].proof.serialize_public(), public);
+        assert!(stored[0].proof.v.is_zero());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn receipt_with_oversized_balance_does_not_encode() {
+        let (_, mut receipt) = publish(Epoch::new(1, 1_560_000_000, 580_000), BigUint::from(1u8));
+        receipt.balance = BigUint::from(u64::max_value()) + 1u8;
+
+        assert!(receipt.to_bytes().is_err());
+    }
+
+    /// Fresh directory unique to this process, so parallel test runs don't share it
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "provisions_archive_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Publish a bundle with alice and bob as customers, returning alice's receipt
+    fn publish(epoch: Epoch, balance: BigUint) -> (SolvencyBundle, CustomerReceipt) {
+        let (g, h) = (crate::g(), crate::h());
+        let x = Field256::from(1);
+        let y = point_mul(Point::g(), &x);
+        // Assets exactly cover the liabilities
+        let assets = vec![AssetProof::create(
+            Some(x),
+            &y,
+            &balance + BigUint::from(5u8),
+            &g,
+            &h,
+        )];
+        let liabilities = vec![
+            LiabilityProof::create(b"alice", &balance, g.clone(), h.clone()),
+            LiabilityProof::create(b"bob", &BigUint::from(5u8), g, h),
+        ];
+
+        let bundle =
+            SolvencyBundle::create(epoch, &assets, &[], &liabilities, Params::default()).unwrap();
+        let tree = CommitmentTree::new(&liabilities).unwrap();
+        let path = tree.prove(liabilities[0].cid()).unwrap();
+        let receipt = CustomerReceipt {
+            balance,
+            path,
+            proof: EpochProof::new(
+                epoch,
+                LiabilityProof::deserialize(&liabilities[0].serialize()),
+            ),
+        };
+        let receipt = CustomerReceipt::deserialize(&receipt.to_bytes().unwrap());
+
+        (bundle, receipt)
+    }
+}
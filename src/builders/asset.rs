@@ -1,55 +1,296 @@
-use crate::data_source::asset::AssetDataSource;
-use crate::proofs::AssetProof;
-use crate::secp256k1::Point;
+use crate::builders::{checkpoint, read_batch, resume, thread_pool, DEFAULT_BATCH_SIZE};
+use crate::data_source::asset::AssetInput;
+use crate::data_source::{Proof, ProofSink};
+use crate::params::Params;
+use crate::proofs::{AssetProof, MultisigAssetProof};
+use crate::signer::Signer;
+use rayon::prelude::*;
 
+/// Generates an asset proof for every record of the input, followed by a multisig asset proof
+/// for every multisig output. Keys held by the signer are claimed as owned and the signer performs
+/// the private key dependent step of their proofs.
+///
+/// Records are read in batches of `batch_size`, proven in parallel on a dedicated thread pool and
+/// written to the sink in the same order they were read.
 pub struct AssetProofBuilder<'a> {
-    ds: &'a mut AssetDataSource,
-    g: Point,
-    h: Point,
+    input: &'a mut dyn AssetInput,
+    sink: &'a mut dyn ProofSink,
+    signer: &'a dyn Signer,
+    params: Params,
+    batch_size: usize,
+    threads: usize,
 }
 
 impl<'a> AssetProofBuilder<'a> {
-    pub fn new(ds: &'a mut AssetDataSource) -> AssetProofBuilder {
+    pub fn new(
+        input: &'a mut dyn AssetInput,
+        sink: &'a mut dyn ProofSink,
+        signer: &'a dyn Signer,
+    ) -> AssetProofBuilder<'a> {
         AssetProofBuilder {
-            ds,
-            g: crate::g(),
-            h: crate::h(),
+            input,
+            sink,
+            signer,
+            params: Params::default(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            threads: 0,
         }
     }
 
-    pub fn build(&mut self) {
+    /// Parameters to create the proofs with, the default ones unless set
+    pub fn params(mut self, params: Params) -> AssetProofBuilder<'a> {
+        self.params = params;
+        self
+    }
+
+    /// Number of records to read from the input before proving them.
+    pub fn batch_size(mut self, batch_size: usize) -> AssetProofBuilder<'a> {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Number of threads to prove on. Zero uses one thread per logical CPU.
+    pub fn threads(mut self, threads: usize) -> AssetProofBuilder<'a> {
+        self.threads = threads;
+        self
+    }
+
+    /// Generate proofs for every remaining record. When the sink holds a checkpoint from an
+    /// earlier run over the same input, generation resumes after the last stored proof. Fails if
+    /// the input can't be read, the signer refuses to respond for one of its keys or the sink
+    /// can't store a proof.
+    pub fn build(&mut self) -> Result<(), &'static str> {
+        let input_hash = self.input.input_hash();
+        let input = &mut *self.input;
+        let mut offset = resume(self.sink, input_hash, |offset| input.seek(offset).is_ok())?;
+        let pool = thread_pool(self.threads);
+
         loop {
-            match self.ds.next() {
-                None => break,
+            let batch_size = self.batch_size;
+            let batch = read_batch(batch_size, || self.input.next());
+            if batch.is_empty() {
+                break;
+            }
+            let consumed = batch.len();
 
-                Some(asset) => {
-                    let proof = AssetProof::create(asset.0, &asset.1, asset.2, &self.g, &self.h);
-                    self.ds.put_proof(proof).expect("put works");
-                }
+            let (signer, g, h) = (self.signer, &self.params.g, &self.params.h);
+            let proofs: Vec<AssetProof> = pool.install(|| {
+                batch
+                    .into_par_iter()
+                    .map(|asset| AssetProof::create_with_signer(&asset.0, asset.1, signer, g, h))
+                    .collect::<Result<Vec<AssetProof>, &'static str>>()
+            })?;
+
+            offset += consumed;
+            for proof in proofs {
+                self.sink
+                    .put_proof(Proof::Asset(proof))
+                    .map_err(|_| "unable to store proof")?;
             }
+            checkpoint(self.sink, input_hash, offset)?;
+        }
+        if self.input.error().is_some() {
+            return Err("unable to read input");
         }
+
+        loop {
+            let batch_size = self.batch_size;
+            let batch = read_batch(batch_size, || self.input.next_multisig());
+            if batch.is_empty() {
+                break;
+            }
+            let consumed = batch.len();
+
+            let (signer, g, h) = (self.signer, &self.params.g, &self.params.h);
+            let proofs: Vec<MultisigAssetProof> = pool.install(|| {
+                batch
+                    .into_par_iter()
+                    .map(|asset| {
+                        MultisigAssetProof::create_with_signer(
+                            &asset.0, asset.1, asset.2, signer, g, h,
+                        )
+                    })
+                    .collect::<Result<Vec<MultisigAssetProof>, &'static str>>()
+            })?;
+
+            offset += consumed;
+            for proof in proofs {
+                self.sink
+                    .put_proof(Proof::Multisig(proof))
+                    .map_err(|_| "unable to store proof")?;
+            }
+            checkpoint(self.sink, input_hash, offset)?;
+        }
+        if self.input.error().is_some() {
+            return Err("unable to read input");
+        }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::data_source::asset::AssetData;
+    use crate::data_source::asset::{AssetData, MultisigAssetData};
+    use crate::data_source::{Checkpoint, MemoryProofStore};
     use crate::fields::Field256;
     use crate::secp256k1::{point_mul, Point};
+    use crate::serialization::Serialize;
+    use crate::signer::MemorySigner;
     use num_bigint::BigUint;
+    use sha2::{Digest, Sha256};
 
     #[test]
     fn asset_proof_builder_builds_all_assets() {
         let asset_count = 2;
-        let assets = gen_assets(asset_count);
-        let mut asset_ds = MemoryAssetDataSource::new(assets);
-        let mut builder = AssetProofBuilder::new(&mut asset_ds);
+        let mut assets = gen_assets(asset_count).into_iter();
+        let mut store = MemoryProofStore::new();
+        let signer = MemorySigner::new(vec![Field256::from(1)]);
+        let mut builder = AssetProofBuilder::new(&mut assets, &mut store, &signer);
+
+        builder.build().unwrap();
+
+        assert_eq!(assets.len(), 0);
+        assert_eq!(store.assets.len(), asset_count);
+    }
+
+    #[test]
+    fn asset_proof_builder_preserves_order_across_batches() {
+        let asset_count = 7;
+        let xs: Vec<Field256> = (1..=asset_count).map(Field256::from).collect();
+        let assets: Vec<AssetData> = xs
+            .iter()
+            .map(|x| (point_mul(Point::g(), x), BigUint::from(10u8)))
+            .collect();
+        let expected: Vec<Point> = assets.iter().map(|asset| asset.0.clone()).collect();
+        let signer = MemorySigner::new(xs);
+        let mut input = assets.into_iter();
+        let mut store = MemoryProofStore::new();
+        let mut builder = AssetProofBuilder::new(&mut input, &mut store, &signer)
+            .batch_size(3)
+            .threads(2);
 
-        builder.build();
+        builder.build().unwrap();
 
-        assert_eq!(asset_ds.assets.len(), 0);
-        assert_eq!(asset_ds.proofs.len(), asset_count);
+        let proven: Vec<Point> = store.assets.iter().map(|proof| proof.y.clone()).collect();
+        assert_eq!(proven, expected);
+    }
+
+    #[test]
+    fn asset_proof_builder_resumes_from_checkpoint() {
+        let mut input = MemoryAssetInput::new(gen_assets(5));
+        let input_hash = input.input_hash().unwrap();
+        let mut store = MemoryProofStore::new();
+        store
+            .put_checkpoint(&Checkpoint {
+                offset: 2,
+                input_hash,
+            })
+            .unwrap();
+        let signer = MemorySigner::new(vec![]);
+        let mut builder = AssetProofBuilder::new(&mut input, &mut store, &signer).batch_size(2);
+
+        builder.build().unwrap();
+
+        assert_eq!(store.assets.len(), 3);
+        assert_eq!(
+            store.checkpoint(),
+            Some(Checkpoint {
+                offset: 5,
+                input_hash
+            })
+        );
+    }
+
+    #[test]
+    fn asset_proof_builder_resumes_into_multisig_assets() {
+        let keys: Vec<Point> = (1..4)
+            .map(|i| point_mul(Point::g(), &Field256::from(i)))
+            .collect();
+        let mut input = MemoryAssetInput::new(gen_assets(3));
+        input.multisigs = vec![
+            (keys.clone(), 2, BigUint::from(10u8)),
+            (keys[1..].to_vec(), 1, BigUint::from(20u8)),
+        ];
+        let input_hash = input.input_hash().unwrap();
+        let mut store = MemoryProofStore::new();
+        // Interrupted after every plain asset and the first multisig output
+        store
+            .put_checkpoint(&Checkpoint {
+                offset: 4,
+                input_hash,
+            })
+            .unwrap();
+        let signer = MemorySigner::new(vec![]);
+        let mut builder = AssetProofBuilder::new(&mut input, &mut store, &signer).batch_size(2);
+
+        builder.build().unwrap();
+
+        assert_eq!(store.assets.len(), 0);
+        assert_eq!(store.multisigs.len(), 1);
+        assert_eq!(store.multisigs[0].keys, keys[1..].to_vec());
+        assert_eq!(
+            store.checkpoint(),
+            Some(Checkpoint {
+                offset: 5,
+                input_hash
+            })
+        );
+    }
+
+    #[test]
+    fn asset_proof_builder_rejects_changed_input() {
+        let mut input = MemoryAssetInput::new(gen_assets(5));
+        let mut store = MemoryProofStore::new();
+        store
+            .put_checkpoint(&Checkpoint {
+                offset: 2,
+                input_hash: [0; 32],
+            })
+            .unwrap();
+        let signer = MemorySigner::new(vec![]);
+        let mut builder = AssetProofBuilder::new(&mut input, &mut store, &signer);
+
+        assert!(builder.build().is_err());
+        assert_eq!(store.assets.len(), 0);
+    }
+
+    #[test]
+    fn asset_proof_builder_claims_signer_keys() {
+        let signer = MemorySigner::new(vec![Field256::from(2)]);
+        let mut assets = (1..4).map(|i| {
+            (
+                point_mul(Point::g(), &Field256::from(i)),
+                BigUint::from(10u8),
+            )
+        });
+        let mut store = MemoryProofStore::new();
+        let mut builder = AssetProofBuilder::new(&mut assets, &mut store, &signer);
+
+        builder.build().unwrap();
+
+        assert_eq!(store.assets.len(), 3);
+        assert!(store.assets.iter().all(|proof| proof.verify()));
+    }
+
+    #[test]
+    fn asset_proof_builder_builds_multisig_assets() {
+        let keys: Vec<Point> = (1..4)
+            .map(|i| point_mul(Point::g(), &Field256::from(i)))
+            .collect();
+        let signer = MemorySigner::new(vec![Field256::from(1), Field256::from(2)]);
+        let mut input = MemoryAssetInput::new(gen_assets(2));
+        input.multisigs = vec![(keys, 2, BigUint::from(10u8))];
+        let mut store = MemoryProofStore::new();
+        let mut builder = AssetProofBuilder::new(&mut input, &mut store, &signer);
+
+        builder.build().unwrap();
+
+        assert_eq!(store.assets.len(), 2);
+        assert_eq!(store.multisigs.len(), 1);
+        assert!(store.multisigs[0].verify());
     }
 
     fn gen_assets(num: usize) -> Vec<AssetData> {
@@ -59,26 +300,26 @@ mod tests {
                 let x = Field256::from(1);
                 let y = point_mul(Point::g(), &x);
                 let bal = BigUint::from(10u8);
-                (Some(x), y, bal)
+                (y, bal)
             })
             .collect()
     }
 
-    struct MemoryAssetDataSource {
+    struct MemoryAssetInput {
         assets: Vec<AssetData>,
-        proofs: Vec<AssetProof>,
+        multisigs: Vec<MultisigAssetData>,
     }
 
-    impl MemoryAssetDataSource {
-        fn new(assets: Vec<AssetData>) -> MemoryAssetDataSource {
-            MemoryAssetDataSource {
+    impl MemoryAssetInput {
+        fn new(assets: Vec<AssetData>) -> MemoryAssetInput {
+            MemoryAssetInput {
                 assets,
-                proofs: vec![],
+                multisigs: vec![],
             }
         }
     }
 
-    impl AssetDataSource for MemoryAssetDataSource {
+    impl AssetInput for MemoryAssetInput {
         fn next(&mut self) -> Option<AssetData> {
             if self.assets.len() > 0 {
                 let asset = self.assets.remove(0);
@@ -88,8 +329,39 @@ mod tests {
             }
         }
 
-        fn put_proof(&mut self, proof: AssetProof) -> Result<(), &str> {
-            self.proofs.push(proof);
+        fn next_multisig(&mut self) -> Option<MultisigAssetData> {
+            if self.multisigs.len() > 0 {
+                Some(self.multisigs.remove(0))
+            } else {
+                None
+            }
+        }
+
+        fn input_hash(&mut self) -> Option<[u8; 32]> {
+            let mut hasher = Sha256::new();
+            for asset in self.assets.iter() {
+                hasher.input(asset.0.serialize());
+                hasher.input(asset.1.to_bytes_be());
+            }
+            for multisig in self.multisigs.iter() {
+                for key in multisig.0.iter() {
+                    hasher.input(key.serialize());
+                }
+                hasher.input(&(multisig.1 as u64).to_be_bytes());
+                hasher.input(multisig.2.to_bytes_be());
+            }
+            let mut out = [0; 32];
+            out.copy_from_slice(&hasher.result()[..]);
+            Some(out)
+        }
+
+        fn seek(&mut self, offset: usize) -> Result<(), &str> {
+            if offset > self.assets.len() + self.multisigs.len() {
+                return Err("offset past end of input");
+            }
+            let plain = offset.min(self.assets.len());
+            self.assets.drain(..plain);
+            self.multisigs.drain(..(offset - plain));
             Ok(())
         }
     }
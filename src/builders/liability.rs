@@ -1,34 +1,92 @@
-use crate::data_source::liability::LiabilityDataSource;
+use crate::builders::{checkpoint, read_batch, resume, thread_pool, DEFAULT_BATCH_SIZE};
+use crate::data_source::liability::LiabilityInput;
+use crate::data_source::{Proof, ProofSink};
+use crate::params::Params;
 use crate::proofs::LiabilityProof;
-use crate::secp256k1::Point;
+use rayon::prelude::*;
 
+/// Generates a liability proof for every record of the input.
+///
+/// Records are read in batches of `batch_size`, proven in parallel on a dedicated thread pool and
+/// written to the sink in the same order they were read.
 pub struct LiabilityProofBuilder<'a> {
-    ds: &'a mut LiabilityDataSource,
-    g: Point,
-    h: Point,
+    input: &'a mut dyn LiabilityInput,
+    sink: &'a mut dyn ProofSink,
+    params: Params,
+    batch_size: usize,
+    threads: usize,
 }
 
 impl<'a> LiabilityProofBuilder<'a> {
-    pub fn new(ds: &'a mut LiabilityDataSource) -> LiabilityProofBuilder {
+    pub fn new(
+        input: &'a mut dyn LiabilityInput,
+        sink: &'a mut dyn ProofSink,
+    ) -> LiabilityProofBuilder<'a> {
         LiabilityProofBuilder {
-            ds,
-            g: crate::g(),
-            h: crate::h(),
+            input,
+            sink,
+            params: Params::default(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            threads: 0,
         }
     }
 
-    pub fn build(&mut self) {
+    /// Parameters to create the proofs with, the default ones unless set
+    pub fn params(mut self, params: Params) -> LiabilityProofBuilder<'a> {
+        self.params = params;
+        self
+    }
+
+    /// Number of records to read from the input before proving them.
+    pub fn batch_size(mut self, batch_size: usize) -> LiabilityProofBuilder<'a> {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Number of threads to prove on. Zero uses one thread per logical CPU.
+    pub fn threads(mut self, threads: usize) -> LiabilityProofBuilder<'a> {
+        self.threads = threads;
+        self
+    }
+
+    /// Generate proofs for every remaining record. When the sink holds a checkpoint from an
+    /// earlier run over the same input, generation resumes after the last stored proof. Fails if
+    /// the input can't be read or the sink can't store a proof.
+    pub fn build(&mut self) -> Result<(), &'static str> {
+        let input_hash = self.input.input_hash();
+        let input = &mut *self.input;
+        let mut offset = resume(self.sink, input_hash, |offset| input.seek(offset).is_ok())?;
+        let pool = thread_pool(self.threads);
+
         loop {
-            match self.ds.next() {
-                None => break,
-
-                Some(liab) => {
-                    let proof =
-                        LiabilityProof::create(&liab.0, &liab.1, self.g.clone(), self.h.clone());
-                    self.ds.put_proof(proof).expect("put works");
-                }
+            let batch_size = self.batch_size;
+            let batch = read_batch(batch_size, || self.input.next());
+            if batch.is_empty() {
+                break;
             }
+
+            let params = &self.params;
+            let proofs: Vec<LiabilityProof> = pool.install(|| {
+                batch
+                    .into_par_iter()
+                    .map(|liab| LiabilityProof::create_with_params(&liab.0, &liab.1, params))
+                    .collect()
+            });
+
+            offset += proofs.len();
+            for proof in proofs {
+                self.sink
+                    .put_proof(Proof::Liability(proof))
+                    .map_err(|_| "unable to store proof")?;
+            }
+            checkpoint(self.sink, input_hash, offset)?;
+        }
+        if self.input.error().is_some() {
+            return Err("unable to read input");
         }
+
+        Ok(())
     }
 }
 
@@ -36,20 +94,91 @@ impl<'a> LiabilityProofBuilder<'a> {
 mod tests {
     use super::*;
     use crate::data_source::liability::LiabilityData;
+    use crate::data_source::{Checkpoint, MemoryProofStore};
     use crate::fields::Field256;
+    use crate::serialization::Serialize;
     use num_bigint::BigUint;
+    use sha2::{Digest, Sha256};
 
     #[test]
     fn liability_proof_builder_builds_all_liabilitys() {
         let liability_count = 2;
-        let liabilitys = gen_liabilitys(liability_count);
-        let mut liability_ds = MemoryLiabilityDataSource::new(liabilitys);
-        let mut builder = LiabilityProofBuilder::new(&mut liability_ds);
+        let mut liabilitys = gen_liabilitys(liability_count).into_iter();
+        let mut store = MemoryProofStore::new();
+        let mut builder = LiabilityProofBuilder::new(&mut liabilitys, &mut store);
 
-        builder.build();
+        builder.build().unwrap();
 
-        assert_eq!(liability_ds.liabilitys.len(), 0);
-        assert_eq!(liability_ds.proofs.len(), liability_count);
+        assert_eq!(liabilitys.len(), 0);
+        assert_eq!(store.liabilities.len(), liability_count);
+    }
+
+    #[test]
+    fn liability_proof_builder_preserves_order_across_batches() {
+        let liabilitys = gen_liabilitys(5);
+        let mut input = liabilitys.clone().into_iter();
+        let mut store = MemoryProofStore::new();
+        let mut builder = LiabilityProofBuilder::new(&mut input, &mut store)
+            .batch_size(2)
+            .threads(2);
+
+        builder.build().unwrap();
+
+        assert_eq!(store.liabilities.len(), liabilitys.len());
+        for (proof, liab) in store.liabilities.iter().zip(liabilitys.iter()) {
+            assert!(proof.verify_as_customer(&liab.0, &liab.1));
+        }
+    }
+
+    #[test]
+    fn liability_proof_builder_uses_params() {
+        let liabilitys = gen_liabilitys(2);
+        let params = Params::new(b"test").balance_bits(32);
+        let mut input = liabilitys.clone().into_iter();
+        let mut store = MemoryProofStore::new();
+        let mut builder = LiabilityProofBuilder::new(&mut input, &mut store).params(params.clone());
+
+        builder.build().unwrap();
+
+        for (proof, liab) in store.liabilities.iter().zip(liabilitys.iter()) {
+            assert!(proof.verify_as_customer(&liab.0, &liab.1));
+            assert_eq!(proof.serialize_public().len(), 32 + 195 * 32);
+            let proof = LiabilityProof::deserialize_with(&proof.serialize(), &params);
+            assert!(proof.verify_as_customer(&liab.0, &liab.1));
+            assert!(proof.check_with(&params).is_ok());
+            assert!(proof.check_with(&Params::default()).is_err());
+        }
+    }
+
+    #[test]
+    fn liability_proof_builder_resumes_from_checkpoint() {
+        let liabilitys = gen_liabilitys(4);
+        let mut input = MemoryLiabilityInput {
+            liabilitys: liabilitys.clone(),
+        };
+        let input_hash = input.input_hash().unwrap();
+        let mut store = MemoryProofStore::new();
+        store
+            .put_checkpoint(&Checkpoint {
+                offset: 3,
+                input_hash,
+            })
+            .unwrap();
+        let mut builder = LiabilityProofBuilder::new(&mut input, &mut store);
+
+        builder.build().unwrap();
+
+        assert_eq!(store.liabilities.len(), 1);
+        assert!(store.liabilities[0].verify_as_customer(&liabilitys[3].0, &liabilitys[3].1));
+    }
+
+    #[test]
+    fn liability_proof_builder_reports_sink_errors() {
+        let mut input = gen_liabilitys(2).into_iter();
+        let mut sink = FailingSink;
+        let mut builder = LiabilityProofBuilder::new(&mut input, &mut sink);
+
+        assert_eq!(builder.build(), Err("unable to store proof"));
     }
 
     fn gen_liabilitys(num: usize) -> Vec<LiabilityData> {
@@ -63,21 +192,19 @@ mod tests {
             .collect()
     }
 
-    struct MemoryLiabilityDataSource {
-        liabilitys: Vec<LiabilityData>,
-        proofs: Vec<LiabilityProof>,
-    }
+    struct FailingSink;
 
-    impl MemoryLiabilityDataSource {
-        fn new(liabilitys: Vec<LiabilityData>) -> MemoryLiabilityDataSource {
-            MemoryLiabilityDataSource {
-                liabilitys,
-                proofs: vec![],
-            }
+    impl ProofSink for FailingSink {
+        fn put_proof(&mut self, _proof: Proof) -> Result<(), &str> {
+            Err("disk full")
         }
     }
 
-    impl LiabilityDataSource for MemoryLiabilityDataSource {
+    struct MemoryLiabilityInput {
+        liabilitys: Vec<LiabilityData>,
+    }
+
+    impl LiabilityInput for MemoryLiabilityInput {
         fn next(&mut self) -> Option<LiabilityData> {
             if self.liabilitys.len() > 0 {
                 let liability = self.liabilitys.remove(0);
@@ -87,8 +214,19 @@ mod tests {
             }
         }
 
-        fn put_proof(&mut self, proof: LiabilityProof) -> Result<(), &str> {
-            self.proofs.push(proof);
+        fn input_hash(&mut self) -> Option<[u8; 32]> {
+            let mut hasher = Sha256::new();
+            for liability in self.liabilitys.iter() {
+                hasher.input(&liability.0);
+                hasher.input(liability.1.to_bytes_be());
+            }
+            let mut out = [0; 32];
+            out.copy_from_slice(&hasher.result()[..]);
+            Some(out)
+        }
+
+        fn seek(&mut self, offset: usize) -> Result<(), &str> {
+            self.liabilitys.drain(..offset);
             Ok(())
         }
     }
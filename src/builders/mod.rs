@@ -1,5 +1,77 @@
+use crate::data_source::{Checkpoint, ProofSink};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
 pub mod asset;
 pub mod liability;
+pub mod sum_tree;
 
 pub use self::asset::AssetProofBuilder;
 pub use self::liability::LiabilityProofBuilder;
+pub use self::sum_tree::SumTreeBuilder;
+
+/// Number of records read from an input and proven together by default.
+const DEFAULT_BATCH_SIZE: usize = 1024;
+
+/// Build the pool proofs are generated on. A thread count of zero uses one thread per logical
+/// CPU.
+fn thread_pool(threads: usize) -> ThreadPool {
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("thread pool")
+}
+
+/// Seek the input past the records proven by an earlier run, returning the offset to continue
+/// from. Inputs without a hash always start from the beginning.
+fn resume<S>(
+    sink: &dyn ProofSink,
+    input_hash: Option<[u8; 32]>,
+    seek: S,
+) -> Result<usize, &'static str>
+where
+    S: FnOnce(usize) -> bool,
+{
+    let input_hash = match input_hash {
+        None => return Ok(0),
+        Some(input_hash) => input_hash,
+    };
+
+    match sink.checkpoint() {
+        None => Ok(0),
+        Some(ref checkpoint) if checkpoint.input_hash != input_hash => {
+            Err("input changed since checkpoint")
+        }
+        Some(checkpoint) => {
+            if !seek(checkpoint.offset) {
+                return Err("unable to seek to checkpoint");
+            }
+            Ok(checkpoint.offset)
+        }
+    }
+}
+
+/// Store a checkpoint after the first `offset` records of the input, if it has a hash.
+fn checkpoint(
+    sink: &mut dyn ProofSink,
+    input_hash: Option<[u8; 32]>,
+    offset: usize,
+) -> Result<(), &'static str> {
+    match input_hash {
+        None => Ok(()),
+        Some(input_hash) => sink
+            .put_checkpoint(&Checkpoint { offset, input_hash })
+            .map_err(|_| "unable to store checkpoint"),
+    }
+}
+
+/// Read up to `size` records, stopping early when `next` runs out.
+fn read_batch<T, F: FnMut() -> Option<T>>(size: usize, mut next: F) -> Vec<T> {
+    let mut batch = Vec::with_capacity(size);
+    while batch.len() < size {
+        match next() {
+            None => break,
+            Some(record) => batch.push(record),
+        }
+    }
+    batch
+}
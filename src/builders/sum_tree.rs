@@ -0,0 +1,76 @@
+use crate::data_source::liability::LiabilityInput;
+use crate::fields::Field256;
+use crate::proofs::{SumLeaf, SumTree};
+
+/// Builds a Merkle sum tree over every record of the input, as an alternative to
+/// generating a `LiabilityProof` per record with the `LiabilityProofBuilder`.
+///
+/// No proofs are written to a sink. The returned tree holds every customer's leaf and
+/// hands out their inclusion proofs.
+pub struct SumTreeBuilder<'a> {
+    input: &'a mut dyn LiabilityInput,
+}
+
+impl<'a> SumTreeBuilder<'a> {
+    pub fn new(input: &'a mut dyn LiabilityInput) -> SumTreeBuilder {
+        SumTreeBuilder { input }
+    }
+
+    /// Read every remaining record and build the tree. Fails if the input can't be read, a
+    /// balance doesn't fit in the balance range or the input is empty.
+    pub fn build(&mut self) -> Result<SumTree, &'static str> {
+        let mut leaves = vec![];
+        while let Some((identifier, balance)) = self.input.next() {
+            leaves.push(SumLeaf::new(&identifier, &balance, Field256::rand().value)?);
+        }
+        if self.input.error().is_some() {
+            return Err("unable to read input");
+        }
+
+        SumTree::new(leaves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_source::liability::LiabilityData;
+    use crate::params::Params;
+    use crate::proofs::{LiabilityModeReport, SumTreeProof};
+    use crate::serialization::{Deserialize, Serialize};
+    use num_bigint::BigUint;
+
+    #[test]
+    fn sum_tree_builder_builds_all_liabilities() {
+        let liabilities: Vec<LiabilityData> = (0..3u8)
+            .map(|i| (vec![i], BigUint::from(10u8 + i)))
+            .collect();
+        let mut input = liabilities.clone().into_iter();
+
+        let tree = SumTreeBuilder::new(&mut input).build().unwrap();
+
+        assert_eq!(tree.root().sum, 33);
+        assert_eq!(
+            LiabilityModeReport::new(&tree, &Params::default()).customers,
+            3
+        );
+        for (identifier, balance) in liabilities.iter() {
+            let leaf = tree
+                .leaves()
+                .iter()
+                .find(|leaf| BigUint::from(leaf.balance) == *balance)
+                .unwrap();
+            let proof = tree.prove(&leaf.cid).unwrap();
+            let proof = SumTreeProof::deserialize(&proof.serialize());
+
+            assert!(proof.verify_as_customer(identifier, leaf.balance, tree.root()));
+        }
+    }
+
+    #[test]
+    fn sum_tree_builder_rejects_empty_input() {
+        let mut input = Vec::<LiabilityData>::new().into_iter();
+
+        assert!(SumTreeBuilder::new(&mut input).build().is_err());
+    }
+}
@@ -1,52 +1,168 @@
-use crate::fields::Field256;
-use crate::proofs::AssetProof;
-use crate::secp256k1::{point_mul, Point};
-use crate::serialization::Serialize;
+use crate::secp256k1::Point;
 use num_bigint::BigUint;
-use rocksdb::DB;
 
-pub type AssetData = (Option<Field256>, Point, BigUint);
+#[cfg(feature = "rocksdb")]
+pub use self::rocks::Rocks;
 
-pub trait AssetDataSource {
+/// A public key and its balance. Private keys are held by a `Signer` rather than the input.
+pub type AssetData = (Point, BigUint);
+
+/// An output controlled by m of n keys: (keys, m, balance)
+pub type MultisigAssetData = (Vec<Point>, usize, BigUint);
+
+/// Input records to generate asset proofs for. Any iterator over `AssetData` is an input without
+/// multisig outputs or support for resuming.
+pub trait AssetInput {
     /// Retrieve next asset to generate proof for
     fn next(&mut self) -> Option<AssetData>;
 
-    /// Store the generated proof in storage
-    fn put_proof(&mut self, proof: AssetProof) -> Result<(), &str>;
+    /// Retrieve next multisig output to generate proof for, called once `next` is exhausted
+    fn next_multisig(&mut self) -> Option<MultisigAssetData> {
+        None
+    }
+
+    /// Hash of the complete input, used to detect inputs changing between an interrupted run and
+    /// its resumption. Inputs returning `None` always start from the beginning.
+    fn input_hash(&mut self) -> Option<[u8; 32]> {
+        None
+    }
+
+    /// Position the input so the next record returned is the one at `offset`. Offsets count the
+    /// records returned by `next` followed by those returned by `next_multisig`. By default the
+    /// records before `offset` are read and dropped.
+    fn seek(&mut self, offset: usize) -> Result<(), &str> {
+        for _ in 0..offset {
+            if self.next().is_none() && self.next_multisig().is_none() {
+                return Err("offset past end of input");
+            }
+        }
+        Ok(())
+    }
+
+    /// Error that ended the input before its last record, checked once `next` and
+    /// `next_multisig` return `None`
+    fn error(&self) -> Option<&str> {
+        None
+    }
 }
 
-pub struct Rocks {
-    db: DB,
-    assets_to_generate: usize,
+impl<I: Iterator<Item = AssetData>> AssetInput for I {
+    fn next(&mut self) -> Option<AssetData> {
+        Iterator::next(self)
+    }
 }
 
-impl Rocks {
-    pub fn new(assets_to_generate: usize, path: &str) -> Rocks {
-        let db = DB::open_default(path).unwrap();
+#[cfg(feature = "rocksdb")]
+mod rocks {
+    use crate::data_source::{Checkpoint, Proof, ProofSink, ProofSource};
+    use crate::params::Params;
+    use crate::serialization::{Deserialize, Serialize};
+    use rocksdb::{Direction, IteratorMode, WriteBatch, DB};
+    use std::collections::BTreeMap;
+
+    /// Key the checkpoint is stored under. Proofs are keyed by their tag, which is never the first
+    /// byte of this, followed by their key.
+    const CHECKPOINT_KEY: &[u8] = b"checkpoint";
 
-        Rocks {
-            db,
-            assets_to_generate,
+    /// Proof store in a RocksDB database. Proofs are read back in the order of their keys.
+    ///
+    /// Proofs are held back and written in one batch with the next checkpoint, by `commit` or
+    /// when the store is dropped, so an interrupted batch leaves no proofs behind for the resumed
+    /// run to store twice. Storing a second proof with the same tag and key fails.
+    pub struct Rocks {
+        db: DB,
+        /// Proofs stored since the last checkpoint, by key
+        pending: BTreeMap<Vec<u8>, Vec<u8>>,
+        /// Key of the last proof read back
+        last_key: Option<Vec<u8>>,
+        params: Params,
+    }
+
+    impl Rocks {
+        pub fn open(path: &str) -> Result<Rocks, &'static str> {
+            let db = DB::open_default(path).map_err(|_| "unable to open database")?;
+
+            Ok(Rocks {
+                db,
+                pending: BTreeMap::new(),
+                last_key: None,
+                params: Params::default(),
+            })
+        }
+
+        /// Parameters the stored proofs were created with, the default ones unless set
+        pub fn params(mut self, params: Params) -> Rocks {
+            self.params = params;
+            self
+        }
+
+        /// Write the proofs stored since the last checkpoint
+        pub fn commit(&mut self) -> Result<(), &'static str> {
+            if self.pending.is_empty() {
+                return Ok(());
+            }
+
+            let mut batch = WriteBatch::default();
+            for (key, value) in self.pending.iter() {
+                batch.put(key, value).map_err(|_| "bad write")?;
+            }
+            self.db.write(batch).map_err(|_| "bad write")?;
+            self.pending.clear();
+            Ok(())
         }
     }
-}
 
-impl AssetDataSource for Rocks {
-    fn next(&mut self) -> Option<AssetData> {
-        if self.assets_to_generate == 0 {
-            return None;
+    impl ProofSink for Rocks {
+        fn put_proof(&mut self, proof: Proof) -> Result<(), &str> {
+            let mut key = vec![proof.tag()];
+            key.extend(proof.key());
+            let stored = self.db.get(&key).map_err(|_| "bad read")?;
+            if stored.is_some() || self.pending.contains_key(&key) {
+                return Err("proof with the same key already stored");
+            }
+
+            self.pending.insert(key, proof.serialize());
+            Ok(())
+        }
+
+        fn checkpoint(&self) -> Option<Checkpoint> {
+            match self.db.get(CHECKPOINT_KEY) {
+                Ok(Some(bytes)) => Some(Checkpoint::deserialize(&bytes)),
+                _ => None,
+            }
+        }
+
+        /// Write the checkpoint in the same batch as the proofs before it
+        fn put_checkpoint(&mut self, checkpoint: &Checkpoint) -> Result<(), &str> {
+            self.pending
+                .insert(CHECKPOINT_KEY.to_vec(), checkpoint.serialize());
+            self.commit()
         }
+    }
 
-        self.assets_to_generate -= 1;
-        let x = Field256::from(1);
-        let y = point_mul(Point::g(), &x);
-        let bal = BigUint::from(10u8);
-        Some((Some(x), y, bal))
+    impl ProofSource for Rocks {
+        fn next_proof(&mut self) -> Result<Option<Proof>, &'static str> {
+            let mode = match self.last_key {
+                None => IteratorMode::Start,
+                Some(ref key) => IteratorMode::From(key, Direction::Forward),
+            };
+
+            for (key, value) in self.db.iterator(mode) {
+                let already_read = self.last_key.as_ref().map_or(false, |last| **last == *key);
+                if already_read || *key == *CHECKPOINT_KEY {
+                    continue;
+                }
+
+                self.last_key = Some(key.to_vec());
+                return Proof::decode(key[0], &value, &self.params).map(Some);
+            }
+            Ok(None)
+        }
     }
 
-    fn put_proof(&mut self, proof: AssetProof) -> Result<(), &str> {
-        self.db
-            .put(proof.y.serialize(), proof.serialize())
-            .map_err(|_| "bad write")
+    impl Drop for Rocks {
+        fn drop(&mut self) {
+            let _ = self.commit();
+        }
     }
 }
@@ -0,0 +1,180 @@
+use crate::data_source::asset::{AssetData, AssetInput};
+use crate::secp256k1::Point;
+use crate::serialization::Serialize;
+use crate::signer::{Keystore, Signer};
+use num_bigint::BigUint;
+use secp256k1::PublicKey;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+
+/// Asset input over a list of public keys and balances, proven with the private keys held in a
+/// `Keystore`.
+///
+/// The list is the full anonymity set: keys held by the keystore are claimed as owned and every
+/// other key is proven as a decoy. Private keys are only decrypted by the keystore when the
+/// builder asks it to respond for one of them. Every key in the keystore must appear in the list,
+/// otherwise its balance would silently be left out of the proof.
+pub struct KeystoreAssetInput<'a> {
+    keystore: &'a Keystore,
+    assets: Vec<AssetData>,
+    position: usize,
+}
+
+impl<'a> KeystoreAssetInput<'a> {
+    pub fn new(
+        assets: Vec<AssetData>,
+        keystore: &'a Keystore,
+    ) -> Result<KeystoreAssetInput<'a>, &'static str> {
+        let mut listed = HashSet::new();
+        for asset in assets.iter() {
+            if !listed.insert(asset.0.serialize()) {
+                return Err("duplicate key in asset list");
+            }
+        }
+        for y in keystore.public_keys() {
+            if !listed.contains(&y.serialize()) {
+                return Err("keystore key missing from asset list");
+            }
+        }
+
+        Ok(KeystoreAssetInput {
+            keystore,
+            assets,
+            position: 0,
+        })
+    }
+
+    /// Read the asset list from `path`. Each line holds a hex encoded compressed public key and
+    /// its balance in satoshis separated by whitespace. Blank lines and lines starting with `#`
+    /// are skipped.
+    pub fn open(path: &str, keystore: &'a Keystore) -> io::Result<KeystoreAssetInput<'a>> {
+        let assets = parse_asset_list(&fs::read_to_string(path)?)?;
+        KeystoreAssetInput::new(assets, keystore).map_err(invalid_data)
+    }
+
+    /// The keystore to pass to the builder as its signer
+    pub fn signer(&self) -> &'a Keystore {
+        self.keystore
+    }
+
+    /// Number of listed keys held by the keystore
+    pub fn owned(&self) -> usize {
+        self.assets
+            .iter()
+            .filter(|asset| self.keystore.has_key(&asset.0))
+            .count()
+    }
+}
+
+impl<'a> AssetInput for KeystoreAssetInput<'a> {
+    fn next(&mut self) -> Option<AssetData> {
+        let asset = self.assets.get(self.position).cloned();
+        if asset.is_some() {
+            self.position += 1;
+        }
+        asset
+    }
+
+    fn input_hash(&mut self) -> Option<[u8; 32]> {
+        let mut hasher = Sha256::new();
+        for (y, bal) in self.assets.iter() {
+            let bal = bal.to_bytes_be();
+            hasher.input(&y.serialize());
+            hasher.input(&(bal.len() as u64).to_be_bytes());
+            hasher.input(&bal);
+        }
+        let mut out = [0; 32];
+        out.copy_from_slice(&hasher.result()[..]);
+        Some(out)
+    }
+
+    fn seek(&mut self, offset: usize) -> Result<(), &str> {
+        if offset > self.assets.len() {
+            return Err("offset past end of input");
+        }
+
+        self.position = offset;
+        Ok(())
+    }
+}
+
+fn parse_asset_list(list: &str) -> io::Result<Vec<AssetData>> {
+    let mut assets = vec![];
+    for line in list.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let (y, bal) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(y), Some(bal), None) => (y, bal),
+            _ => return Err(invalid_data("expected a public key and balance")),
+        };
+        let y = hex::decode(y).map_err(|_| invalid_data("public key is not hex"))?;
+        let y = PublicKey::from_slice(&y).map_err(|_| invalid_data("invalid public key"))?;
+        let bal = BigUint::parse_bytes(bal.as_bytes(), 10)
+            .ok_or_else(|| invalid_data("invalid balance"))?;
+        assets.push((Point::from(y), bal));
+    }
+    Ok(assets)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(all(test, feature = "builders"))]
+mod tests {
+    use super::*;
+    use crate::builders::AssetProofBuilder;
+    use crate::data_source::MemoryProofStore;
+    use crate::fields::Field256;
+    use crate::secp256k1::point_mul;
+
+    #[test]
+    fn keystore_asset_input_claims_keystore_keys() {
+        let mut keystore = Keystore::create_with_params("hunter2", 4, 8, 1);
+        let owned = keystore.insert(&Field256::from(7));
+        let decoy = point_mul(Point::g(), &Field256::from(8));
+        let list = format!(
+            "# key balance\n{} 10\n\n{} 20\n",
+            hex::encode(decoy.serialize()),
+            hex::encode(owned.serialize())
+        );
+        let assets = parse_asset_list(&list).unwrap();
+
+        let mut input = KeystoreAssetInput::new(assets, &keystore).unwrap();
+        assert_eq!(input.owned(), 1);
+        let signer = input.signer();
+        let mut store = MemoryProofStore::new();
+        AssetProofBuilder::new(&mut input, &mut store, signer)
+            .build()
+            .unwrap();
+
+        let proofs = &store.assets;
+        assert_eq!(proofs.len(), 2);
+        assert!(proofs.iter().all(|proof| proof.verify()));
+        assert_eq!(proofs[0].y, decoy);
+        assert_eq!(proofs[1].y, owned);
+    }
+
+    #[test]
+    fn keystore_asset_input_requires_every_keystore_key() {
+        let mut keystore = Keystore::create_with_params("hunter2", 4, 8, 1);
+        keystore.insert(&Field256::from(7));
+        let decoy = point_mul(Point::g(), &Field256::from(8));
+
+        let result = KeystoreAssetInput::new(vec![(decoy, BigUint::from(10u8))], &keystore);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn asset_list_rejects_malformed_lines() {
+        assert!(parse_asset_list("02abcd").is_err());
+        assert!(parse_asset_list("zz 10").is_err());
+        assert!(parse_asset_list(&format!("{} ten", hex::encode([2u8; 33]))).is_err());
+    }
+}
@@ -1,12 +1,32 @@
-use crate::proofs::LiabilityProof;
 use num_bigint::BigUint;
 
 pub type LiabilityData = (Vec<u8>, BigUint);
 
-pub trait LiabilityDataSource {
+/// Input records to generate liability proofs for. Any iterator over `LiabilityData` is an input
+/// without support for resuming.
+pub trait LiabilityInput {
     /// Retrieve next liability to generate proof for
     fn next(&mut self) -> Option<LiabilityData>;
 
-    /// Store the generated proof in storage
-    fn put_proof(&mut self, proof: LiabilityProof) -> Result<(), &str>;
+    /// Hash of the complete input, used to detect inputs changing between an interrupted run and
+    /// its resumption. Inputs returning `None` always start from the beginning.
+    fn input_hash(&mut self) -> Option<[u8; 32]> {
+        None
+    }
+
+    /// Position the input so the next call to `next` returns the record at `offset`.
+    fn seek(&mut self, offset: usize) -> Result<(), &str> {
+        Err("seek not supported")
+    }
+
+    /// Error that ended the input before its last record, checked once `next` returns `None`
+    fn error(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl<I: Iterator<Item = LiabilityData>> LiabilityInput for I {
+    fn next(&mut self) -> Option<LiabilityData> {
+        Iterator::next(self)
+    }
 }
@@ -0,0 +1,70 @@
+use crate::data_source::{Checkpoint, Proof, ProofSink, ProofSource};
+use crate::proofs::{AssetProof, LiabilityProof, MultisigAssetProof};
+use std::collections::VecDeque;
+
+/// Proof store holding every proof in memory, by kind in the order they were stored.
+///
+/// As a source it hands the proofs out, asset proofs first, then multisig asset proofs and
+/// liability proofs, removing them from the store.
+#[derive(Default)]
+pub struct MemoryProofStore {
+    pub assets: Vec<AssetProof>,
+    pub multisigs: Vec<MultisigAssetProof>,
+    pub liabilities: Vec<LiabilityProof>,
+    checkpoint: Option<Checkpoint>,
+    /// Proofs taken out of the store and not handed out yet
+    reading: VecDeque<Proof>,
+}
+
+impl MemoryProofStore {
+    pub fn new() -> MemoryProofStore {
+        MemoryProofStore::default()
+    }
+
+    /// Read every proof from `source`, e.g. to verify a published proof store
+    pub fn load(source: &mut dyn ProofSource) -> Result<MemoryProofStore, &'static str> {
+        let mut store = MemoryProofStore::new();
+        while let Some(proof) = source.next_proof()? {
+            store.push(proof);
+        }
+        Ok(store)
+    }
+
+    fn push(&mut self, proof: Proof) {
+        match proof {
+            Proof::Asset(proof) => self.assets.push(proof),
+            Proof::Multisig(proof) => self.multisigs.push(proof),
+            Proof::Liability(proof) => self.liabilities.push(proof),
+        }
+    }
+}
+
+impl ProofSink for MemoryProofStore {
+    fn put_proof(&mut self, proof: Proof) -> Result<(), &str> {
+        self.push(proof);
+        Ok(())
+    }
+
+    fn checkpoint(&self) -> Option<Checkpoint> {
+        self.checkpoint.clone()
+    }
+
+    fn put_checkpoint(&mut self, checkpoint: &Checkpoint) -> Result<(), &str> {
+        self.checkpoint = Some(checkpoint.clone());
+        Ok(())
+    }
+}
+
+impl ProofSource for MemoryProofStore {
+    /// Takes every stored proof out at once and hands them out one at a time, so reading back
+    /// is linear in the number of proofs
+    fn next_proof(&mut self) -> Result<Option<Proof>, &'static str> {
+        if self.reading.is_empty() {
+            let reading = &mut self.reading;
+            reading.extend(self.assets.drain(..).map(Proof::Asset));
+            reading.extend(self.multisigs.drain(..).map(Proof::Multisig));
+            reading.extend(self.liabilities.drain(..).map(Proof::Liability));
+        }
+        Ok(self.reading.pop_front())
+    }
+}
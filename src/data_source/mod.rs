@@ -1 +1,192 @@
+use crate::params::Params;
+use crate::proofs::{AssetProof, LiabilityProof, MultisigAssetProof};
+use crate::serialization::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
 pub mod asset;
+#[cfg(feature = "keystore")]
+pub mod keystore;
+pub mod liability;
+pub mod memory;
+pub mod segment;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+pub use self::memory::MemoryProofStore;
+
+/// A proof written to or read from a proof store
+pub enum Proof {
+    Asset(AssetProof),
+    Multisig(MultisigAssetProof),
+    Liability(LiabilityProof),
+}
+
+impl Proof {
+    /// Tag identifying the kind of proof in the encodings of the stores
+    pub fn tag(&self) -> u8 {
+        match self {
+            Proof::Asset(_) => 1,
+            Proof::Multisig(_) => 2,
+            Proof::Liability(_) => 3,
+        }
+    }
+
+    /// Key the proof is stored under: the public key of an asset proof, the hash of the
+    /// threshold and keys of a multisig asset proof or the customer id of a liability proof.
+    /// Like y for an asset proof, the threshold and keys identify the multisig script whose
+    /// balance the proof is checked against. Keys are only unique among proofs with the same tag.
+    pub fn key(&self) -> Vec<u8> {
+        match self {
+            Proof::Asset(proof) => proof.y.serialize(),
+            Proof::Multisig(proof) => {
+                let mut hasher = Sha256::new();
+                hasher.input([proof.threshold as u8]);
+                for key in proof.keys.iter() {
+                    hasher.input(key.serialize());
+                }
+                hasher.result().to_vec()
+            }
+            Proof::Liability(proof) => proof.cid().to_vec(),
+        }
+    }
+
+    /// Decode the serialized proof tagged with `tag`, created with `params`
+    pub fn decode(tag: u8, bytes: &[u8], params: &Params) -> Result<Proof, &'static str> {
+        match tag {
+            1 => Ok(Proof::Asset(AssetProof::deserialize_with(bytes, params))),
+            2 => MultisigAssetProof::read(bytes, params).map(Proof::Multisig),
+            3 => Ok(Proof::Liability(LiabilityProof::deserialize_with(
+                bytes, params,
+            ))),
+            _ => Err("unknown proof tag"),
+        }
+    }
+
+    /// Encoding of the public part of the proof, without the secrets only the exchange or the
+    /// customer may know, so it can be published
+    pub fn serialize_public(&self) -> Vec<u8> {
+        match self {
+            Proof::Asset(proof) => proof.serialize_public(),
+            Proof::Multisig(proof) => proof.serialize_public(),
+            Proof::Liability(proof) => proof.serialize_public(),
+        }
+    }
+
+    /// Decode the public encoding of a proof tagged with `tag`, created with `params`. The secrets
+    /// are left zero.
+    pub fn decode_public(tag: u8, bytes: &[u8], params: &Params) -> Result<Proof, &'static str> {
+        match tag {
+            1 => Ok(Proof::Asset(AssetProof::deserialize_public_with(
+                bytes, params,
+            ))),
+            2 => MultisigAssetProof::read_public(bytes, params).map(Proof::Multisig),
+            3 => Ok(Proof::Liability(LiabilityProof::deserialize_public_with(
+                bytes, params,
+            ))),
+            _ => Err("unknown proof tag"),
+        }
+    }
+}
+
+impl Serialize for Proof {
+    /// Encodes the proof itself, without its tag
+    fn serialize(&self) -> Vec<u8> {
+        match self {
+            Proof::Asset(proof) => proof.serialize(),
+            Proof::Multisig(proof) => proof.serialize(),
+            Proof::Liability(proof) => proof.serialize(),
+        }
+    }
+}
+
+/// Storage the builders write generated proofs to
+pub trait ProofSink {
+    /// Store the generated proof
+    fn put_proof(&mut self, proof: Proof) -> Result<(), &str>;
+
+    /// The most recently stored checkpoint, if any.
+    fn checkpoint(&self) -> Option<Checkpoint> {
+        None
+    }
+
+    /// Durably store a checkpoint. Only called once every proof before `checkpoint.offset` has
+    /// been stored.
+    fn put_checkpoint(&mut self, checkpoint: &Checkpoint) -> Result<(), &str> {
+        Ok(())
+    }
+}
+
+/// Storage published proofs are read back from for verification
+pub trait ProofSource {
+    /// Retrieve the next stored proof, `None` once every proof has been read
+    fn next_proof(&mut self) -> Result<Option<Proof>, &'static str>;
+}
+
+/// Progress of a proof generation run, stored by the proof sink so an interrupted run can be
+/// resumed from the last durably stored proof.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    /// Number of input records whose proofs have been stored
+    pub offset: usize,
+    /// Hash of the complete input, recorded when the run started
+    pub input_hash: [u8; 32],
+}
+
+impl Serialize for Checkpoint {
+    /// Encodes into 8 + 32 = 40 bytes
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = vec![];
+        out.extend(&(self.offset as u64).to_be_bytes());
+        out.extend(&self.input_hash);
+        out
+    }
+}
+
+impl Deserialize for Checkpoint {
+    fn deserialize(bytes: &[u8]) -> Checkpoint {
+        let mut offset = [0; 8];
+        offset.copy_from_slice(&bytes[0..8]);
+        let mut input_hash = [0; 32];
+        input_hash.copy_from_slice(&bytes[8..40]);
+
+        Checkpoint {
+            offset: u64::from_be_bytes(offset) as usize,
+            input_hash,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fields::Field256;
+    use crate::secp256k1::{point_mul, Point};
+    use num_bigint::BigUint;
+
+    #[test]
+    fn checkpoint_serialization() {
+        let checkpoint = Checkpoint {
+            offset: 1234,
+            input_hash: [7; 32],
+        };
+        let checkpoint2 = Checkpoint::deserialize(&checkpoint.serialize());
+
+        assert_eq!(checkpoint, checkpoint2);
+    }
+
+    #[test]
+    fn multisig_key_includes_threshold() {
+        let (g, h) = (crate::g(), crate::h());
+        let xs: Vec<Field256> = (1..4).map(Field256::from).collect();
+        let keys: Vec<Point> = xs.iter().map(|x| point_mul(Point::g(), x)).collect();
+        let known: Vec<Option<Field256>> = xs.into_iter().map(Some).collect();
+        let multisig = |threshold| {
+            let proof =
+                MultisigAssetProof::create(&known, &keys, threshold, BigUint::from(10u8), &g, &h);
+            Proof::Multisig(proof)
+        };
+
+        assert_eq!(multisig(2).key(), multisig(2).key());
+        assert_ne!(multisig(2).key(), multisig(3).key());
+    }
+}
@@ -0,0 +1,662 @@
+use crate::data_source::{Checkpoint, Proof, ProofSink, ProofSource};
+use crate::params::Params;
+use crate::proofs::{AssetProof, LiabilityProof, MultisigAssetProof};
+use crate::secp256k1::Point;
+use crate::serialization::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const INDEX_FILE: &str = "index";
+const CHECKPOINT_FILE: &str = "checkpoint";
+/// Segments are rotated once they grow past this size
+const DEFAULT_SEGMENT_BYTES: u64 = 64 * 1024 * 1024;
+/// Largest number of keys of a stored multisig asset proof, as in a standard multisig script
+const MAX_MULTISIG_KEYS: usize = 16;
+
+fn segment_path(dir: &Path, segment: u32) -> PathBuf {
+    dir.join(format!("segment-{:06}", segment))
+}
+
+/// Append-only proof store backed by plain files, for exchanges that don't want the RocksDB
+/// dependency. Only the public encodings of the proofs are stored, see `Proof::serialize_public`,
+/// so the directory it writes can be published as is.
+///
+/// Proofs are appended to numbered segment files as a 4 byte big-endian length followed by the
+/// proof's tag and its public encoding. Every proof is also appended to an index file mapping
+/// its tag and key, see `Proof::key`, to its segment and offset:
+///
+/// | Bytes | Content |
+/// | --- | --- |
+/// | 1 | Key length |
+/// | key length | Tag followed by the key |
+/// | 4 | Segment number (big-endian) |
+/// | 8 | Offset of the record in the segment (big-endian) |
+///
+/// Checkpoints are stored along with the position of the end of the segments and the index at
+/// the time, so an interrupted run can be resumed without storing any proof twice.
+pub struct SegmentStore {
+    dir: PathBuf,
+    position: Position,
+    max_segment_bytes: u64,
+    max_record_len: usize,
+    writer: BufWriter<File>,
+    index: BufWriter<File>,
+    checkpoint: Option<Checkpoint>,
+}
+
+impl SegmentStore {
+    /// Open the store in `dir` for proofs created with the default parameters, see `open_with`
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<SegmentStore> {
+        SegmentStore::open_with(dir, &Params::default())
+    }
+
+    /// Open the store in `dir` for proofs created with `params`, creating it if needed. Proofs
+    /// stored after the last checkpoint are dropped, or without a checkpoint every record after
+    /// the last indexed one.
+    pub fn open_with<P: AsRef<Path>>(dir: P, params: &Params) -> io::Result<SegmentStore> {
+        let dir = dir.as_ref().to_path_buf();
+        let max_record_len = max_record_len(params);
+        fs::create_dir_all(&dir)?;
+
+        let (checkpoint, position) = match fs::read(dir.join(CHECKPOINT_FILE)) {
+            Ok(ref bytes) if bytes.len() == 40 + Position::ENCODED_LEN => (
+                Some(Checkpoint::deserialize(&bytes[0..40])),
+                Position::deserialize(&bytes[40..]),
+            ),
+            Ok(_) => return Err(invalid_data("truncated checkpoint")),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                (None, Position::last(&dir, max_record_len)?)
+            }
+            Err(e) => return Err(e),
+        };
+
+        let mut segment = position.segment + 1;
+        while segment_path(&dir, segment).exists() {
+            fs::remove_file(segment_path(&dir, segment))?;
+            segment += 1;
+        }
+        let writer = open_at(&segment_path(&dir, position.segment), position.segment_len)?;
+        let index = open_at(&dir.join(INDEX_FILE), position.index_len)?;
+
+        Ok(SegmentStore {
+            dir,
+            position,
+            max_segment_bytes: DEFAULT_SEGMENT_BYTES,
+            max_record_len,
+            writer,
+            index,
+            checkpoint,
+        })
+    }
+
+    /// Size in bytes after which a new segment is started
+    pub fn max_segment_bytes(mut self, max_segment_bytes: u64) -> SegmentStore {
+        self.max_segment_bytes = max_segment_bytes;
+        self
+    }
+
+    /// Append the public encoding of a proof and index it. Fails for proofs larger than any
+    /// proof created with the parameters of the store, such as multisig asset proofs for more
+    /// than 16 keys.
+    pub fn append(&mut self, proof: &Proof) -> io::Result<()> {
+        let mut key = vec![proof.tag()];
+        key.extend(proof.key());
+        let mut record = vec![proof.tag()];
+        record.extend(proof.serialize_public());
+        if record.len() > self.max_record_len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "proof too large for the store",
+            ));
+        }
+
+        let record_len = 4 + record.len() as u64;
+        let position = &mut self.position;
+        if position.segment_len > 0 && position.segment_len + record_len > self.max_segment_bytes {
+            self.writer.flush()?;
+            self.writer.get_ref().sync_data()?;
+            position.segment += 1;
+            position.segment_len = 0;
+            self.writer = BufWriter::new(File::create(segment_path(&self.dir, position.segment))?);
+        }
+
+        self.writer
+            .write_all(&(record.len() as u32).to_be_bytes())?;
+        self.writer.write_all(&record)?;
+
+        self.index.write_all(&[key.len() as u8])?;
+        self.index.write_all(&key)?;
+        self.index.write_all(&position.segment.to_be_bytes())?;
+        self.index.write_all(&position.segment_len.to_be_bytes())?;
+
+        position.segment_len += record_len;
+        position.index_len += 1 + key.len() as u64 + 12;
+        Ok(())
+    }
+
+    /// Write buffered proofs to disk and wait until they are durably stored
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()?;
+        self.index.flush()?;
+        self.index.get_ref().sync_data()
+    }
+
+    /// Durably store the checkpoint along with the current position, once the proofs before it
+    /// are durably stored. The checkpoint file is replaced atomically.
+    fn write_checkpoint(&mut self, checkpoint: &Checkpoint) -> io::Result<()> {
+        self.sync()?;
+
+        let mut bytes = checkpoint.serialize();
+        bytes.extend(self.position.serialize());
+        let tmp = self.dir.join(format!("{}.tmp", CHECKPOINT_FILE));
+        let mut file = File::create(&tmp)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp, self.dir.join(CHECKPOINT_FILE))?;
+
+        self.checkpoint = Some(checkpoint.clone());
+        Ok(())
+    }
+}
+
+impl ProofSink for SegmentStore {
+    /// Appends the proof, which is only durably stored by the next checkpoint or `sync`
+    fn put_proof(&mut self, proof: Proof) -> Result<(), &str> {
+        self.append(&proof).map_err(|_| "bad write")
+    }
+
+    fn checkpoint(&self) -> Option<Checkpoint> {
+        self.checkpoint.clone()
+    }
+
+    fn put_checkpoint(&mut self, checkpoint: &Checkpoint) -> Result<(), &str> {
+        self.write_checkpoint(checkpoint).map_err(|_| "bad write")
+    }
+}
+
+impl Drop for SegmentStore {
+    fn drop(&mut self) {
+        let _ = self.sync();
+    }
+}
+
+/// End of the segments and the index: the last segment, its length and the length of the index
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Position {
+    segment: u32,
+    segment_len: u64,
+    index_len: u64,
+}
+
+impl Position {
+    const ENCODED_LEN: usize = 4 + 8 + 8;
+
+    /// End of the last index entry whose record is complete in `dir`. Records after it aren't
+    /// indexed and are dropped along with it.
+    fn last(dir: &Path, max_record_len: usize) -> io::Result<Position> {
+        let bytes = match fs::read(dir.join(INDEX_FILE)) {
+            Ok(bytes) => bytes,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+
+        // The segment and the index are flushed separately, so the last entries may point past
+        // the end of their segment
+        let mut entries = parse_index(&bytes);
+        while let Some((_, (segment, offset), index_len)) = entries.pop() {
+            let mut file = match File::open(segment_path(dir, segment)) {
+                Ok(file) => file,
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            file.seek(SeekFrom::Start(offset))?;
+            match read_record(&mut file, max_record_len) {
+                Ok(Some(record)) => {
+                    return Ok(Position {
+                        segment,
+                        segment_len: offset + 4 + record.len() as u64,
+                        index_len: index_len as u64,
+                    })
+                }
+                Ok(None) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(Position {
+            segment: 0,
+            segment_len: 0,
+            index_len: 0,
+        })
+    }
+}
+
+impl Serialize for Position {
+    /// Encodes into 4 + 8 + 8 = 20 bytes
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = self.segment.to_be_bytes().to_vec();
+        out.extend(&self.segment_len.to_be_bytes());
+        out.extend(&self.index_len.to_be_bytes());
+        out
+    }
+}
+
+impl Deserialize for Position {
+    fn deserialize(bytes: &[u8]) -> Position {
+        let mut segment = [0; 4];
+        segment.copy_from_slice(&bytes[0..4]);
+        let mut segment_len = [0; 8];
+        segment_len.copy_from_slice(&bytes[4..12]);
+        let mut index_len = [0; 8];
+        index_len.copy_from_slice(&bytes[12..20]);
+
+        Position {
+            segment: u32::from_be_bytes(segment),
+            segment_len: u64::from_be_bytes(segment_len),
+            index_len: u64::from_be_bytes(index_len),
+        }
+    }
+}
+
+/// Open `path` for appending after its first `len` bytes, dropping the rest
+fn open_at(path: &Path, len: u64) -> io::Result<BufWriter<File>> {
+    let mut file = OpenOptions::new().create(true).write(true).open(path)?;
+    if file.metadata()?.len() < len {
+        return Err(invalid_data("proof store is shorter than its checkpoint"));
+    }
+    file.set_len(len)?;
+    file.seek(SeekFrom::Start(len))?;
+    Ok(BufWriter::new(file))
+}
+
+/// Reads the proofs written by a `SegmentStore`. As a proof source it reads every proof in the
+/// order it was written.
+pub struct SegmentReader {
+    dir: PathBuf,
+    index: HashMap<Vec<u8>, (u32, u64)>,
+    cursor: Option<Records>,
+    params: Params,
+}
+
+impl SegmentReader {
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<SegmentReader> {
+        let dir = dir.as_ref().to_path_buf();
+        let bytes = fs::read(dir.join(INDEX_FILE))?;
+
+        let entries = parse_index(&bytes);
+        if entries.last().map_or(0, |entry| entry.2) != bytes.len() {
+            return Err(invalid_data("truncated index"));
+        }
+        let index = entries
+            .into_iter()
+            .map(|(key, location, _)| (key, location))
+            .collect();
+
+        Ok(SegmentReader {
+            dir,
+            index,
+            cursor: None,
+            params: Params::default(),
+        })
+    }
+
+    /// Parameters the proofs were created with, the default ones unless set
+    pub fn params(mut self, params: Params) -> SegmentReader {
+        self.params = params;
+        self
+    }
+
+    /// Number of indexed proofs
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// The public encoding of the proof with the given tag stored under `key`
+    pub fn get(&self, tag: u8, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let mut tagged = vec![tag];
+        tagged.extend(key);
+        let (segment, offset) = match self.index.get(&tagged) {
+            Some(location) => *location,
+            None => return Ok(None),
+        };
+
+        let mut file = File::open(segment_path(&self.dir, segment))?;
+        file.seek(SeekFrom::Start(offset))?;
+        match read_record(&mut file, max_record_len(&self.params))? {
+            Some(ref record) if record.first() == Some(&tag) => Ok(Some(record[1..].to_vec())),
+            _ => Err(invalid_data("index points to a missing record")),
+        }
+    }
+
+    pub fn asset_proof(&self, y: &Point) -> io::Result<Option<AssetProof>> {
+        Ok(self
+            .get(1, &y.serialize())?
+            .map(|bytes| AssetProof::deserialize_public_with(&bytes, &self.params)))
+    }
+
+    pub fn liability_proof(&self, cid: &[u8; 32]) -> io::Result<Option<LiabilityProof>> {
+        Ok(self
+            .get(3, cid)?
+            .map(|bytes| LiabilityProof::deserialize_public_with(&bytes, &self.params)))
+    }
+
+    /// Every record, a tag followed by the public encoding, in the order it was written,
+    /// reading one record at a time
+    pub fn records(&self) -> Records {
+        Records {
+            dir: self.dir.clone(),
+            segment: 0,
+            reader: None,
+            max_record_len: max_record_len(&self.params),
+        }
+    }
+}
+
+impl ProofSource for SegmentReader {
+    fn next_proof(&mut self) -> Result<Option<Proof>, &'static str> {
+        if self.cursor.is_none() {
+            self.cursor = Some(self.records());
+        }
+
+        match self.cursor.as_mut().and_then(Iterator::next) {
+            None => Ok(None),
+            Some(Err(_)) => Err("unable to read segment"),
+            Some(Ok(ref record)) if record.is_empty() => Err("empty record"),
+            Some(Ok(record)) => {
+                Proof::decode_public(record[0], &record[1..], &self.params).map(Some)
+            }
+        }
+    }
+}
+
+/// Iterator over the records of every segment of a store
+pub struct Records {
+    dir: PathBuf,
+    segment: u32,
+    reader: Option<BufReader<File>>,
+    max_record_len: usize,
+}
+
+impl Iterator for Records {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<u8>>> {
+        loop {
+            if self.reader.is_none() {
+                match File::open(segment_path(&self.dir, self.segment)) {
+                    Ok(file) => self.reader = Some(BufReader::new(file)),
+                    Err(ref e) if e.kind() == io::ErrorKind::NotFound => return None,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            let reader = self.reader.as_mut().expect("segment open");
+            match read_record(reader, self.max_record_len) {
+                Ok(Some(record)) => return Some(Ok(record)),
+                Ok(None) => {
+                    self.reader = None;
+                    self.segment += 1;
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Complete entries of an index file in the order they were written, each with the length of
+/// the index up to its end, ignoring a partially written one at the end
+fn parse_index(bytes: &[u8]) -> Vec<(Vec<u8>, (u32, u64), usize)> {
+    let mut entries = vec![];
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let key_len = bytes[pos] as usize;
+        let end = pos + 1 + key_len + 12;
+        if end > bytes.len() {
+            break;
+        }
+
+        let key = bytes[(pos + 1)..(pos + 1 + key_len)].to_vec();
+        let mut segment = [0; 4];
+        segment.copy_from_slice(&bytes[(end - 12)..(end - 8)]);
+        let mut offset = [0; 8];
+        offset.copy_from_slice(&bytes[(end - 8)..end]);
+        let location = (u32::from_be_bytes(segment), u64::from_be_bytes(offset));
+        entries.push((key, location, end));
+        pos = end;
+    }
+    entries
+}
+
+/// Largest record a store for proofs created with `params` holds: a tag followed by the
+/// largest public encoding of a proof
+fn max_record_len(params: &Params) -> usize {
+    let multisig = MultisigAssetProof::public_encoded_len(MAX_MULTISIG_KEYS, MAX_MULTISIG_KEYS / 2)
+        .expect("multisig length fits");
+    let liability = LiabilityProof::public_encoded_len(params);
+    1 + multisig.max(liability).max(AssetProof::PUBLIC_ENCODED_LEN)
+}
+
+/// Read one length prefixed record, or `None` at the end of the segment. Fails for records
+/// longer than `max_len` before allocating them.
+fn read_record<R: Read>(reader: &mut R, max_len: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > max_len {
+        return Err(invalid_data("record longer than any proof"));
+    }
+    let mut record = vec![0; len];
+    reader.read_exact(&mut record)?;
+    Ok(Some(record))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fields::Field256;
+    use crate::secp256k1::point_mul;
+    use num_bigint::BigUint;
+    use num_traits::Zero;
+
+    #[test]
+    fn segment_store_indexes_asset_proofs() {
+        let dir = temp_dir("assets");
+        let (g, h) = (crate::g(), crate::h());
+        let mut store = SegmentStore::open(&dir).unwrap().max_segment_bytes(1000);
+
+        let ys: Vec<Point> = (1..4)
+            .map(|i| point_mul(Point::g(), &Field256::from(i)))
+            .collect();
+        for y in ys.iter() {
+            let proof = AssetProof::create(None, y, BigUint::from(10u8), &g, &h);
+            store.put_proof(Proof::Asset(proof)).unwrap();
+        }
+        store.sync().unwrap();
+
+        // One 526 byte record fits per segment
+        assert!(segment_path(&dir, 2).exists());
+        let mut reader = SegmentReader::open(&dir).unwrap();
+        assert_eq!(reader.len(), 3);
+        for y in ys.iter() {
+            let proof = reader.asset_proof(y).unwrap().unwrap();
+            assert_eq!(&proof.y, y);
+            assert!(proof.verify());
+            assert!(proof.v.is_zero());
+        }
+        let records: Vec<Vec<u8>> = reader.records().collect::<io::Result<_>>().unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].len(), 1 + AssetProof::PUBLIC_ENCODED_LEN);
+
+        for y in ys.iter() {
+            match reader.next_proof().unwrap() {
+                Some(Proof::Asset(proof)) => assert_eq!(&proof.y, y),
+                _ => panic!("expected an asset proof"),
+            }
+        }
+        assert!(reader.next_proof().unwrap().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn segment_store_indexes_liability_proofs() {
+        let dir = temp_dir("liabilities");
+        let (g, h) = (crate::g(), crate::h());
+        let mut store = SegmentStore::open(&dir).unwrap();
+
+        let proof = LiabilityProof::create(b"testuser", &BigUint::from(10u8), g, h);
+        let (cid, public) = (*proof.cid(), proof.serialize_public());
+        store.put_proof(Proof::Liability(proof)).unwrap();
+        store.sync().unwrap();
+
+        // The customer secrets aren't stored
+        let reader = SegmentReader::open(&dir).unwrap();
+        let proof = reader.liability_proof(&cid).unwrap().unwrap();
+        assert_eq!(proof.serialize_public(), public);
+        assert!(proof.verify());
+        assert!(proof.r.is_zero());
+        assert!(reader.liability_proof(&[0; 32]).unwrap().is_none());
+        assert!(reader.get(1, &cid).unwrap().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn segment_reader_reads_proofs_with_params() {
+        let dir = temp_dir("params");
+        let params = Params::new(b"test").balance_bits(32);
+        let mut store = SegmentStore::open_with(&dir, &params).unwrap();
+
+        let proof = LiabilityProof::create_with_params(b"testuser", &BigUint::from(10u8), &params);
+        let cid = *proof.cid();
+        store.put_proof(Proof::Liability(proof)).unwrap();
+        store.sync().unwrap();
+
+        let mut reader = SegmentReader::open(&dir).unwrap().params(params.clone());
+        let proof = reader.liability_proof(&cid).unwrap().unwrap();
+        assert!(proof.check_with(&params).is_ok());
+        match reader.next_proof().unwrap() {
+            Some(Proof::Liability(proof)) => assert!(proof.check_with(&params).is_ok()),
+            _ => panic!("expected a liability proof"),
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn segment_store_resumes_from_checkpoint() {
+        let dir = temp_dir("resume");
+        let (g, h) = (crate::g(), crate::h());
+        let proofs: Vec<LiabilityProof> = (0..4)
+            .map(|i| {
+                let id = format!("user{}", i);
+                LiabilityProof::create(id.as_bytes(), &BigUint::from(1u8), g.clone(), h.clone())
+            })
+            .collect();
+        let checkpoint = Checkpoint {
+            offset: 2,
+            input_hash: [7; 32],
+        };
+
+        // Two records fit per segment
+        let mut store = SegmentStore::open(&dir).unwrap().max_segment_bytes(20_000);
+        for proof in proofs[0..2].iter() {
+            let proof = LiabilityProof::deserialize(&proof.serialize());
+            store.put_proof(Proof::Liability(proof)).unwrap();
+        }
+        store.put_checkpoint(&checkpoint).unwrap();
+        // Stored after the checkpoint by a run that was interrupted
+        let proof = LiabilityProof::deserialize(&proofs[2].serialize());
+        store.put_proof(Proof::Liability(proof)).unwrap();
+        drop(store);
+
+        let mut store = SegmentStore::open(&dir).unwrap().max_segment_bytes(20_000);
+        assert_eq!(store.checkpoint(), Some(checkpoint));
+        assert_eq!(SegmentReader::open(&dir).unwrap().len(), 2);
+        for proof in proofs[2..4].iter() {
+            let proof = LiabilityProof::deserialize(&proof.serialize());
+            store.put_proof(Proof::Liability(proof)).unwrap();
+        }
+        store.sync().unwrap();
+
+        let mut reader = SegmentReader::open(&dir).unwrap();
+        assert_eq!(reader.len(), 4);
+        for proof in proofs.iter() {
+            match reader.next_proof().unwrap() {
+                Some(Proof::Liability(stored)) => assert_eq!(stored.cid(), proof.cid()),
+                _ => panic!("expected a liability proof"),
+            }
+        }
+        assert!(reader.next_proof().unwrap().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn segment_store_drops_unindexed_records() {
+        let dir = temp_dir("unindexed");
+        let (g, h) = (crate::g(), crate::h());
+        let y = point_mul(Point::g(), &Field256::from(1));
+        let mut store = SegmentStore::open(&dir).unwrap();
+        let proof = AssetProof::create(None, &y, BigUint::from(10u8), &g, &h);
+        store.put_proof(Proof::Asset(proof)).unwrap();
+        drop(store);
+
+        // A complete record whose index entry was never written, then a huge length prefix
+        let mut segment = OpenOptions::new()
+            .append(true)
+            .open(segment_path(&dir, 0))
+            .unwrap();
+        segment.write_all(&2u32.to_be_bytes()).unwrap();
+        segment.write_all(&[1, 2]).unwrap();
+        segment.write_all(&u32::MAX.to_be_bytes()).unwrap();
+        drop(segment);
+        let records: Vec<io::Result<Vec<u8>>> =
+            SegmentReader::open(&dir).unwrap().records().collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            records[2].as_ref().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let store = SegmentStore::open(&dir).unwrap();
+        assert_eq!(
+            store.position.segment_len,
+            4 + 1 + AssetProof::PUBLIC_ENCODED_LEN as u64
+        );
+        drop(store);
+        let reader = SegmentReader::open(&dir).unwrap();
+        assert_eq!(reader.records().count(), 1);
+        assert!(reader.asset_proof(&y).unwrap().unwrap().verify());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Fresh directory unique to this process, so parallel test runs don't share it
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "provisions_segment_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+}
@@ -0,0 +1,640 @@
+use crate::data_source::asset::{AssetData, AssetInput};
+use crate::data_source::liability::{LiabilityData, LiabilityInput};
+use crate::data_source::{Checkpoint, Proof, ProofSink, ProofSource};
+use crate::params::Params;
+use crate::proofs::{AssetProof, LiabilityProof};
+use crate::secp256k1::Point;
+use crate::serialization::{Deserialize, Serialize};
+use num_bigint::BigUint;
+use rusqlite::types::{Type, Value};
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension, NO_PARAMS};
+use secp256k1::PublicKey;
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+
+/// Tag and key the checkpoint is stored under in the proofs table. Proof tags start at 1.
+const CHECKPOINT_TAG: u8 = 0;
+const CHECKPOINT_KEY: &[u8] = b"checkpoint";
+/// Number of input rows or proofs read per query
+const DEFAULT_BATCH_SIZE: usize = 1000;
+
+/// Names of the table and columns a SQLite input is read from. Names are interpolated into the
+/// queries as quoted identifiers and must come from trusted configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputTable {
+    /// Table with one row per asset or customer, read in `rowid` order
+    pub table: String,
+    /// Column holding the compressed public key of an asset or the identifier of a customer, as
+    /// a blob or text
+    pub key: String,
+    /// Column holding the balance, as a non-negative integer or decimal text
+    pub balance: String,
+}
+
+impl InputTable {
+    /// `assets(public_key, balance)`
+    pub fn assets() -> InputTable {
+        InputTable {
+            table: "assets".to_string(),
+            key: "public_key".to_string(),
+            balance: "balance".to_string(),
+        }
+    }
+
+    /// `liabilities(identifier, balance)`
+    pub fn liabilities() -> InputTable {
+        InputTable {
+            table: "liabilities".to_string(),
+            key: "identifier".to_string(),
+            balance: "balance".to_string(),
+        }
+    }
+}
+
+/// Asset input reading public keys and balances from a SQLite table
+pub struct SqliteAssetInput {
+    rows: Rows,
+    /// Error that ended the input early
+    error: Option<rusqlite::Error>,
+}
+
+impl SqliteAssetInput {
+    pub fn open(path: &str, table: InputTable) -> rusqlite::Result<SqliteAssetInput> {
+        SqliteAssetInput::new(Connection::open(path)?, table)
+    }
+
+    /// Use an open connection. Fails if a row holds an invalid public key or balance.
+    pub fn new(conn: Connection, table: InputTable) -> rusqlite::Result<SqliteAssetInput> {
+        let rows = Rows::new(conn, table, |key| PublicKey::from_slice(key).is_ok())?;
+        Ok(SqliteAssetInput { rows, error: None })
+    }
+
+    /// Number of rows read per query
+    pub fn batch_size(mut self, batch_size: usize) -> SqliteAssetInput {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.rows.batch_size = batch_size;
+        self
+    }
+}
+
+impl AssetInput for SqliteAssetInput {
+    fn next(&mut self) -> Option<AssetData> {
+        let (key, balance) = match self.rows.next() {
+            Ok(row) => row?,
+            Err(e) => {
+                self.error = Some(e);
+                return None;
+            }
+        };
+        match PublicKey::from_slice(&key) {
+            Ok(y) => Some((Point::from(y), balance)),
+            Err(_) => {
+                self.error = Some(conversion_failure(0, "invalid key"));
+                None
+            }
+        }
+    }
+
+    fn input_hash(&mut self) -> Option<[u8; 32]> {
+        Some(self.rows.input_hash)
+    }
+
+    fn seek(&mut self, offset: usize) -> Result<(), &str> {
+        self.rows.seek(offset)
+    }
+
+    fn error(&self) -> Option<&str> {
+        self.error.as_ref().map(|_| "unable to read input table")
+    }
+}
+
+/// Liability input reading customer identifiers and balances from a SQLite table
+pub struct SqliteLiabilityInput {
+    rows: Rows,
+    /// Error that ended the input early
+    error: Option<rusqlite::Error>,
+}
+
+impl SqliteLiabilityInput {
+    pub fn open(path: &str, table: InputTable) -> rusqlite::Result<SqliteLiabilityInput> {
+        SqliteLiabilityInput::new(Connection::open(path)?, table)
+    }
+
+    /// Use an open connection. Fails if a row holds an empty identifier or invalid balance.
+    pub fn new(conn: Connection, table: InputTable) -> rusqlite::Result<SqliteLiabilityInput> {
+        let rows = Rows::new(conn, table, |key| !key.is_empty())?;
+        Ok(SqliteLiabilityInput { rows, error: None })
+    }
+
+    /// Number of rows read per query
+    pub fn batch_size(mut self, batch_size: usize) -> SqliteLiabilityInput {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.rows.batch_size = batch_size;
+        self
+    }
+}
+
+impl LiabilityInput for SqliteLiabilityInput {
+    fn next(&mut self) -> Option<LiabilityData> {
+        match self.rows.next() {
+            Ok(row) => row,
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    fn input_hash(&mut self) -> Option<[u8; 32]> {
+        Some(self.rows.input_hash)
+    }
+
+    fn seek(&mut self, offset: usize) -> Result<(), &str> {
+        self.rows.seek(offset)
+    }
+
+    fn error(&self) -> Option<&str> {
+        self.error.as_ref().map(|_| "unable to read input table")
+    }
+}
+
+/// Input table reader shared by both inputs. Rows are read a batch at a time, resuming after the
+/// last `rowid` read.
+struct Rows {
+    conn: Connection,
+    table: InputTable,
+    batch_size: usize,
+    input_hash: [u8; 32],
+    rows: VecDeque<(Vec<u8>, BigUint)>,
+    last_rowid: i64,
+}
+
+impl Rows {
+    fn new(
+        conn: Connection,
+        table: InputTable,
+        valid_key: fn(&[u8]) -> bool,
+    ) -> rusqlite::Result<Rows> {
+        let mut rows = Rows {
+            conn,
+            table,
+            batch_size: DEFAULT_BATCH_SIZE,
+            input_hash: [0; 32],
+            rows: VecDeque::new(),
+            last_rowid: i64::min_value(),
+        };
+        rows.input_hash = rows.hash_input(valid_key)?;
+        Ok(rows)
+    }
+
+    /// Validate every input row and hash them in order
+    fn hash_input(&self, valid_key: fn(&[u8]) -> bool) -> rusqlite::Result<[u8; 32]> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT \"{}\", \"{}\" FROM \"{}\" ORDER BY rowid",
+            self.table.key, self.table.balance, self.table.table
+        ))?;
+        let rows = stmt.query_map(NO_PARAMS, |row| decode_row(row.get(0)?, row.get(1)?))?;
+
+        let mut hasher = Sha256::new();
+        for row in rows {
+            let (key, balance) = row?;
+            if !valid_key(&key) {
+                return Err(conversion_failure(0, "invalid key"));
+            }
+
+            let balance = balance.to_bytes_be();
+            hasher.input(&(key.len() as u64).to_be_bytes());
+            hasher.input(&key);
+            hasher.input(&(balance.len() as u64).to_be_bytes());
+            hasher.input(&balance);
+        }
+        let mut out = [0; 32];
+        out.copy_from_slice(&hasher.result()[..]);
+        Ok(out)
+    }
+
+    fn next(&mut self) -> rusqlite::Result<Option<(Vec<u8>, BigUint)>> {
+        if self.rows.is_empty() {
+            self.fetch()?;
+        }
+        Ok(self.rows.pop_front())
+    }
+
+    /// Read the next batch of rows after `last_rowid`
+    fn fetch(&mut self) -> rusqlite::Result<()> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT rowid, \"{}\", \"{}\" FROM \"{}\" WHERE rowid > ?1 ORDER BY rowid LIMIT ?2",
+            self.table.key, self.table.balance, self.table.table
+        ))?;
+        let rows = stmt.query_map(params![self.last_rowid, self.batch_size as i64], |row| {
+            Ok((row.get(0)?, decode_row(row.get(1)?, row.get(2)?)?))
+        })?;
+
+        for row in rows {
+            let (rowid, row) = row?;
+            self.last_rowid = rowid;
+            self.rows.push_back(row);
+        }
+        Ok(())
+    }
+
+    fn seek(&mut self, offset: usize) -> Result<(), &'static str> {
+        self.rows.clear();
+        if offset == 0 {
+            self.last_rowid = i64::min_value();
+            return Ok(());
+        }
+
+        let rowid: Option<i64> = self
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT rowid FROM \"{}\" ORDER BY rowid LIMIT 1 OFFSET ?1",
+                    self.table.table
+                ),
+                params![offset as i64 - 1],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|_| "unable to read input")?;
+        self.last_rowid = rowid.ok_or("offset past end of input")?;
+        Ok(())
+    }
+}
+
+/// Proof store in a SQLite table, created if missing, holding the tag, key and serialized proof
+/// of every proof along with the checkpoint. Pointed at the database an input is read from, a run
+/// is a single self-contained file.
+///
+/// Proofs are written in a transaction that is only committed with a checkpoint, by `commit` or
+/// when the store is dropped, so a resumed run never finds the proofs it stores again already
+/// committed. Storing a second proof with the same tag and key fails. As a source it reads
+/// proofs in the order they were written.
+pub struct SqliteProofStore {
+    conn: Connection,
+    table: String,
+    batch_size: usize,
+    /// Whether a transaction is open
+    writing: bool,
+    /// Proofs read ahead by the source and the `rowid` of the last of them
+    read: VecDeque<(u8, Vec<u8>)>,
+    last_rowid: i64,
+    params: Params,
+}
+
+impl SqliteProofStore {
+    pub fn open(path: &str, table: &str) -> rusqlite::Result<SqliteProofStore> {
+        SqliteProofStore::new(Connection::open(path)?, table)
+    }
+
+    /// Use an open connection. The table name must come from trusted configuration.
+    pub fn new(conn: Connection, table: &str) -> rusqlite::Result<SqliteProofStore> {
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS \"{}\" (
+                tag INTEGER NOT NULL,
+                key BLOB NOT NULL,
+                proof BLOB NOT NULL,
+                PRIMARY KEY (tag, key)
+            )",
+            table
+        ))?;
+
+        Ok(SqliteProofStore {
+            conn,
+            table: table.to_string(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            writing: false,
+            read: VecDeque::new(),
+            last_rowid: i64::min_value(),
+            params: Params::default(),
+        })
+    }
+
+    /// Parameters the stored proofs were created with, the default ones unless set
+    pub fn params(mut self, params: Params) -> SqliteProofStore {
+        self.params = params;
+        self
+    }
+
+    /// Number of proofs read per query
+    pub fn batch_size(mut self, batch_size: usize) -> SqliteProofStore {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// The committed serialized proof with the given tag stored under `key`
+    pub fn get(&self, tag: u8, key: &[u8]) -> rusqlite::Result<Option<Vec<u8>>> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT proof FROM \"{}\" WHERE tag = ?1 AND key = ?2",
+                    self.table
+                ),
+                params![tag, key],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    pub fn asset_proof(&self, y: &Point) -> rusqlite::Result<Option<AssetProof>> {
+        Ok(self
+            .get(1, &y.serialize())?
+            .map(|bytes| AssetProof::deserialize_with(&bytes, &self.params)))
+    }
+
+    pub fn liability_proof(&self, cid: &[u8; 32]) -> rusqlite::Result<Option<LiabilityProof>> {
+        Ok(self
+            .get(3, cid)?
+            .map(|bytes| LiabilityProof::deserialize_with(&bytes, &self.params)))
+    }
+
+    /// Write a row in the open transaction, starting one if needed. `insert` is the statement
+    /// up to the table name.
+    fn put(&mut self, insert: &str, tag: u8, key: &[u8], proof: &[u8]) -> rusqlite::Result<()> {
+        if !self.writing {
+            self.conn.execute_batch("BEGIN")?;
+            self.writing = true;
+        }
+        self.conn.execute(
+            &format!(
+                "{} \"{}\" (tag, key, proof) VALUES (?1, ?2, ?3)",
+                insert, self.table
+            ),
+            params![tag, key, proof],
+        )?;
+        Ok(())
+    }
+
+    /// Commit the proofs written since the last checkpoint
+    pub fn commit(&mut self) -> rusqlite::Result<()> {
+        if self.writing {
+            self.conn.execute_batch("COMMIT")?;
+            self.writing = false;
+        }
+        Ok(())
+    }
+
+    /// Read the next batch of proofs after `last_rowid`
+    fn fetch(&mut self) -> rusqlite::Result<()> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT rowid, tag, proof FROM \"{}\" WHERE tag > ?1 AND rowid > ?2
+             ORDER BY rowid LIMIT ?3",
+            self.table
+        ))?;
+        let rows = stmt.query_map(
+            params![CHECKPOINT_TAG, self.last_rowid, self.batch_size as i64],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+
+        for row in rows {
+            let (rowid, tag, proof) = row?;
+            self.last_rowid = rowid;
+            self.read.push_back((tag, proof));
+        }
+        Ok(())
+    }
+}
+
+impl ProofSink for SqliteProofStore {
+    fn put_proof(&mut self, proof: Proof) -> Result<(), &str> {
+        match self.put("INSERT INTO", proof.tag(), &proof.key(), &proof.serialize()) {
+            Ok(()) => Ok(()),
+            Err(rusqlite::Error::SqliteFailure(ref e, _))
+                if e.code == ErrorCode::ConstraintViolation =>
+            {
+                Err("proof with the same key already stored")
+            }
+            Err(_) => Err("bad write"),
+        }
+    }
+
+    fn checkpoint(&self) -> Option<Checkpoint> {
+        match self.get(CHECKPOINT_TAG, CHECKPOINT_KEY) {
+            Ok(Some(bytes)) => Some(Checkpoint::deserialize(&bytes)),
+            _ => None,
+        }
+    }
+
+    /// Store the checkpoint in the same transaction as the proofs before it
+    fn put_checkpoint(&mut self, checkpoint: &Checkpoint) -> Result<(), &str> {
+        let bytes = checkpoint.serialize();
+        self.put(
+            "INSERT OR REPLACE INTO",
+            CHECKPOINT_TAG,
+            CHECKPOINT_KEY,
+            &bytes,
+        )
+        .and_then(|_| self.commit())
+        .map_err(|_| "bad write")
+    }
+}
+
+impl ProofSource for SqliteProofStore {
+    fn next_proof(&mut self) -> Result<Option<Proof>, &'static str> {
+        if self.read.is_empty() {
+            self.fetch().map_err(|_| "unable to read proofs")?;
+        }
+
+        match self.read.pop_front() {
+            None => Ok(None),
+            Some((tag, proof)) => Proof::decode(tag, &proof, &self.params).map(Some),
+        }
+    }
+}
+
+impl Drop for SqliteProofStore {
+    fn drop(&mut self) {
+        let _ = self.commit();
+    }
+}
+
+fn decode_row(key: Value, balance: Value) -> rusqlite::Result<(Vec<u8>, BigUint)> {
+    let key = match key {
+        Value::Blob(key) => key,
+        Value::Text(key) => key.into_bytes(),
+        _ => return Err(conversion_failure(0, "key must be a blob or text")),
+    };
+    let balance = match balance {
+        Value::Integer(balance) if balance >= 0 => BigUint::from(balance as u64),
+        Value::Text(ref balance) => BigUint::parse_bytes(balance.as_bytes(), 10)
+            .ok_or_else(|| conversion_failure(1, "invalid balance"))?,
+        _ => return Err(conversion_failure(1, "invalid balance")),
+    };
+    Ok((key, balance))
+}
+
+fn conversion_failure(column: usize, msg: &str) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(column, Type::Null, msg.into())
+}
+
+#[cfg(all(test, feature = "builders"))]
+mod tests {
+    use super::*;
+    use crate::builders::{AssetProofBuilder, LiabilityProofBuilder};
+    use crate::fields::Field256;
+    use crate::secp256k1::point_mul;
+    use crate::signer::MemorySigner;
+
+    #[test]
+    fn sqlite_asset_input_feeds_proof_store() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE wallet (pk BLOB, sats INTEGER)")
+            .unwrap();
+        let ys: Vec<Point> = (1..4)
+            .map(|i| point_mul(Point::g(), &Field256::from(i)))
+            .collect();
+        for y in ys.iter() {
+            conn.execute(
+                "INSERT INTO wallet (pk, sats) VALUES (?1, 10)",
+                params![y.serialize()],
+            )
+            .unwrap();
+        }
+
+        let table = InputTable {
+            table: "wallet".to_string(),
+            key: "pk".to_string(),
+            balance: "sats".to_string(),
+        };
+        let mut input = SqliteAssetInput::new(conn, table).unwrap().batch_size(2);
+        let mut store = SqliteProofStore::new(Connection::open_in_memory().unwrap(), "proofs")
+            .unwrap()
+            .batch_size(2);
+        let signer = MemorySigner::new(vec![Field256::from(2)]);
+        AssetProofBuilder::new(&mut input, &mut store, &signer)
+            .batch_size(2)
+            .build()
+            .unwrap();
+
+        for y in ys.iter() {
+            assert!(store.asset_proof(y).unwrap().unwrap().verify());
+        }
+        assert_eq!(store.checkpoint().unwrap().offset, 3);
+        for y in ys.iter() {
+            match store.next_proof().unwrap() {
+                Some(Proof::Asset(proof)) => assert_eq!(&proof.y, y),
+                _ => panic!("expected an asset proof"),
+            }
+        }
+        assert!(store.next_proof().unwrap().is_none());
+    }
+
+    #[test]
+    fn sqlite_liability_input_resumes_from_checkpoint() {
+        let path = temp_path("liabilities");
+        let path = path.to_str().unwrap();
+        Connection::open(path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE liabilities (identifier TEXT, balance TEXT);
+                 INSERT INTO liabilities VALUES ('alice', '10'), ('bob', '20'), ('carol', '30');",
+            )
+            .unwrap();
+
+        let mut input = SqliteLiabilityInput::open(path, InputTable::liabilities()).unwrap();
+        let mut store = SqliteProofStore::open(path, "liability_proofs").unwrap();
+        let input_hash = input.input_hash().unwrap();
+        store
+            .put_checkpoint(&Checkpoint {
+                offset: 2,
+                input_hash,
+            })
+            .unwrap();
+        LiabilityProofBuilder::new(&mut input, &mut store)
+            .build()
+            .unwrap();
+
+        // Only carol's proof is generated, customer ids are salted so find it by reading back
+        let mut stored = vec![];
+        while let Some(proof) = store.next_proof().unwrap() {
+            match proof {
+                Proof::Liability(proof) => stored.push(proof),
+                _ => panic!("expected a liability proof"),
+            }
+        }
+        assert_eq!(stored.len(), 1);
+        assert!(stored[0].verify_as_customer(b"carol", &BigUint::from(30u8)));
+        assert!(!stored[0].verify_as_customer(b"alice", &BigUint::from(10u8)));
+        let cid = *stored[0].cid();
+        assert!(store.liability_proof(&cid).unwrap().is_some());
+
+        drop(store);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn sqlite_proof_store_rejects_duplicate_keys() {
+        let (g, h) = (crate::g(), crate::h());
+        let y = point_mul(Point::g(), &Field256::from(1));
+        let mut store =
+            SqliteProofStore::new(Connection::open_in_memory().unwrap(), "proofs").unwrap();
+
+        let proof = AssetProof::create(None, &y, BigUint::from(10u8), &g, &h);
+        store.put_proof(Proof::Asset(proof)).unwrap();
+        let proof = AssetProof::create(None, &y, BigUint::from(20u8), &g, &h);
+        assert!(store.put_proof(Proof::Asset(proof)).is_err());
+    }
+
+    #[test]
+    fn sqlite_input_reports_read_errors() {
+        let path = temp_path("errors");
+        let path = path.to_str().unwrap();
+        Connection::open(path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE liabilities (identifier TEXT, balance INTEGER);
+                 INSERT INTO liabilities VALUES ('alice', 10);",
+            )
+            .unwrap();
+
+        let mut input = SqliteLiabilityInput::open(path, InputTable::liabilities()).unwrap();
+        Connection::open(path)
+            .unwrap()
+            .execute_batch("DROP TABLE liabilities")
+            .unwrap();
+        let mut store =
+            SqliteProofStore::new(Connection::open_in_memory().unwrap(), "proofs").unwrap();
+
+        assert!(LiabilityProofBuilder::new(&mut input, &mut store)
+            .build()
+            .is_err());
+        assert!(input.error().is_some());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn sqlite_inputs_reject_invalid_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE assets (public_key BLOB, balance INTEGER);
+             INSERT INTO assets VALUES (x'0203', 10);",
+        )
+        .unwrap();
+        assert!(SqliteAssetInput::new(conn, InputTable::assets()).is_err());
+
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE liabilities (identifier TEXT, balance INTEGER);
+             INSERT INTO liabilities VALUES ('alice', -10);",
+        )
+        .unwrap();
+        assert!(SqliteLiabilityInput::new(conn, InputTable::liabilities()).is_err());
+    }
+
+    /// Database path unique to this process, so parallel test runs don't share it
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "provisions_sqlite_{}_{}.db",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+}
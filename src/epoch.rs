@@ -0,0 +1,111 @@
+use crate::serialization::{Deserialize, Serialize};
+
+/// Identifies one publication of proofs. Proofs are expected to be published periodically, each
+/// time under an epoch with a larger id, timestamp and block height than the last.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Epoch {
+    /// Sequence number of the publication
+    pub id: u64,
+    /// Unix time the proofs were generated at
+    pub timestamp: u64,
+    /// Height of the block the asset balances were taken from
+    pub block_height: u64,
+}
+
+impl Epoch {
+    pub fn new(id: u64, timestamp: u64, block_height: u64) -> Epoch {
+        Epoch {
+            id,
+            timestamp,
+            block_height,
+        }
+    }
+
+    /// Whether `next` can follow this epoch: every field must strictly increase, except the
+    /// block height which may stay the same when no block was found in between.
+    pub fn precedes(&self, next: &Epoch) -> bool {
+        self.id < next.id
+            && self.timestamp < next.timestamp
+            && self.block_height <= next.block_height
+    }
+}
+
+impl Serialize for Epoch {
+    /// Encodes into 8 + 8 + 8 = 24 bytes
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = vec![];
+        out.extend(&self.id.to_be_bytes());
+        out.extend(&self.timestamp.to_be_bytes());
+        out.extend(&self.block_height.to_be_bytes());
+        out
+    }
+}
+
+impl Deserialize for Epoch {
+    fn deserialize(bytes: &[u8]) -> Epoch {
+        let mut fields = [[0; 8]; 3];
+        for (field, chunk) in fields.iter_mut().zip(bytes[0..24].chunks(8)) {
+            field.copy_from_slice(chunk);
+        }
+
+        Epoch {
+            id: u64::from_be_bytes(fields[0]),
+            timestamp: u64::from_be_bytes(fields[1]),
+            block_height: u64::from_be_bytes(fields[2]),
+        }
+    }
+}
+
+/// A proof tagged with the epoch it was published in
+#[derive(Debug, PartialEq)]
+pub struct EpochProof<T> {
+    pub epoch: Epoch,
+    pub proof: T,
+}
+
+impl<T> EpochProof<T> {
+    pub fn new(epoch: Epoch, proof: T) -> EpochProof<T> {
+        EpochProof { epoch, proof }
+    }
+}
+
+impl<T: Serialize> Serialize for EpochProof<T> {
+    /// Encodes into 24 bytes followed by the proof
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = self.epoch.serialize();
+        out.extend(self.proof.serialize());
+        out
+    }
+}
+
+impl<T: Deserialize> Deserialize for EpochProof<T> {
+    fn deserialize(bytes: &[u8]) -> EpochProof<T> {
+        EpochProof {
+            epoch: Epoch::deserialize(&bytes[0..24]),
+            proof: T::deserialize(&bytes[24..]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epoch_serialization() {
+        let epoch = Epoch::new(3, 1_560_000_000, 580_000);
+
+        assert_eq!(Epoch::deserialize(&epoch.serialize()), epoch);
+    }
+
+    #[test]
+    fn epochs_must_advance() {
+        let epoch = Epoch::new(3, 1_560_000_000, 580_000);
+
+        assert!(epoch.precedes(&Epoch::new(4, 1_560_086_400, 580_144)));
+        assert!(epoch.precedes(&Epoch::new(4, 1_560_000_600, 580_000)));
+        assert!(!epoch.precedes(&Epoch::new(3, 1_560_086_400, 580_144)));
+        assert!(!epoch.precedes(&Epoch::new(4, 1_560_000_000, 580_144)));
+        assert!(!epoch.precedes(&Epoch::new(4, 1_560_086_400, 579_999)));
+    }
+}
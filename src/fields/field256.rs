@@ -1,4 +1,6 @@
 use crate::bigint::biguint_to_bytes_be;
+#[cfg(feature = "json")]
+use crate::json::{from_hex, FromJson, ToJson};
 use crate::serialization::{Deserialize, Serialize};
 use num_bigint::{BigInt, BigUint, Sign};
 use num_integer::Integer;
@@ -6,6 +8,8 @@ use num_traits::{One, Zero};
 use rand::rngs::OsRng;
 use rand::Rng;
 use secp256k1::constants::CURVE_ORDER;
+#[cfg(feature = "json")]
+use serde_json::Value;
 use std::fmt;
 use std::ops::{Add, Mul, Neg, Sub};
 
@@ -102,6 +106,29 @@ impl Deserialize for Field256 {
     }
 }
 
+#[cfg(feature = "json")]
+impl ToJson for Field256 {
+    /// Hex encoding of the 32 byte serialization
+    fn to_json(&self) -> Value {
+        Value::from(hex::encode(self.serialize()))
+    }
+}
+
+#[cfg(feature = "json")]
+impl FromJson for Field256 {
+    fn from_json(value: &Value) -> Result<Field256, &'static str> {
+        let bytes = from_hex(value)?;
+        if bytes.len() != FIELD_BYTES {
+            return Err("field element must be 32 bytes");
+        }
+        let value = BigUint::from_bytes_be(&bytes);
+        if value >= Field256::p() {
+            return Err("field element out of range");
+        }
+        Ok(Field256::new(value))
+    }
+}
+
 impl From<BigUint> for Field256 {
     fn from(value: BigUint) -> Field256 {
         Field256::new(value)
@@ -0,0 +1,239 @@
+//! JSON representation of the proofs for tooling that can't parse the binary encodings.
+//!
+//! Points are hex encoded in their 33 byte compressed form and field elements as 32 big-endian
+//! bytes. Only the public fields of a proof are represented, so the secrets of asset and
+//! liability proofs never end up in JSON and converting back gives their public encoding.
+
+use crate::params::Params;
+use crate::proofs::{AssetProof, LiabilityProof, SolvencyProof};
+use crate::proofs::{BinaryProof, SchnorrProof};
+use crate::secp256k1::Point;
+use crate::serialization::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+
+pub trait ToJson {
+    fn to_json(&self) -> Value;
+}
+
+pub trait FromJson: Sized {
+    fn from_json(value: &Value) -> Result<Self, &'static str>;
+}
+
+/// The kinds of proof that can be converted between binary and JSON
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProofKind {
+    Binary,
+    Schnorr,
+    Asset,
+    Liability,
+    Solvency,
+}
+
+impl FromStr for ProofKind {
+    type Err = &'static str;
+
+    /// Parse the kind from its lowercase name, e.g. a command line argument
+    fn from_str(name: &str) -> Result<ProofKind, &'static str> {
+        match name {
+            "binary" => Ok(ProofKind::Binary),
+            "schnorr" => Ok(ProofKind::Schnorr),
+            "asset" => Ok(ProofKind::Asset),
+            "liability" => Ok(ProofKind::Liability),
+            "solvency" => Ok(ProofKind::Solvency),
+            _ => Err("unknown proof kind"),
+        }
+    }
+}
+
+/// Convert the binary encoding of a proof of the given kind into pretty printed JSON. Asset and
+/// liability proofs are read from either their full or their public encoding.
+pub fn binary_to_json(kind: ProofKind, bytes: &[u8]) -> Result<String, &'static str> {
+    binary_to_json_with(kind, bytes, &Params::default())
+}
+
+/// Convert the binary encoding of a proof of the given kind, created with `params`, into pretty
+/// printed JSON
+pub fn binary_to_json_with(
+    kind: ProofKind,
+    bytes: &[u8],
+    params: &Params,
+) -> Result<String, &'static str> {
+    let liability_len = LiabilityProof::public_encoded_len(params);
+    let valid = match kind {
+        ProofKind::Binary => bytes.len() == BinaryProof::ENCODED_LEN,
+        ProofKind::Schnorr | ProofKind::Solvency => bytes.len() == 131,
+        ProofKind::Asset => {
+            bytes.len() == AssetProof::ENCODED_LEN || bytes.len() == AssetProof::PUBLIC_ENCODED_LEN
+        }
+        ProofKind::Liability => bytes.len() >= liability_len,
+    };
+    if !valid {
+        return Err("unexpected proof length");
+    }
+    // Decoding assumes valid points, so check them first
+    for i in point_offsets(kind, bytes, liability_len) {
+        Point::try_deserialize(&bytes[i..(i + 33)])?;
+    }
+
+    let value = match kind {
+        ProofKind::Binary => BinaryProof::deserialize_with(bytes, &params.g, &params.h).to_json(),
+        ProofKind::Schnorr => SchnorrProof::deserialize(bytes).to_json(),
+        ProofKind::Asset if bytes.len() == AssetProof::PUBLIC_ENCODED_LEN => {
+            AssetProof::deserialize_public_with(bytes, params).to_json()
+        }
+        ProofKind::Asset => AssetProof::deserialize_with(bytes, params).to_json(),
+        ProofKind::Liability => {
+            LiabilityProof::deserialize_public_with(&bytes[..liability_len], params).to_json()
+        }
+        ProofKind::Solvency => SolvencyProof::deserialize_with(bytes, params).to_json(),
+    };
+    serde_json::to_string_pretty(&value).map_err(|_| "unable to encode JSON")
+}
+
+/// Convert the JSON representation of a proof of the given kind into its binary encoding
+pub fn json_to_binary(kind: ProofKind, json: &str) -> Result<Vec<u8>, &'static str> {
+    json_to_binary_with(kind, json, &Params::default())
+}
+
+/// Convert the JSON representation of a proof of the given kind, created with `params`, into its
+/// binary encoding
+pub fn json_to_binary_with(
+    kind: ProofKind,
+    json: &str,
+    params: &Params,
+) -> Result<Vec<u8>, &'static str> {
+    let value: Value = serde_json::from_str(json).map_err(|_| "invalid JSON")?;
+
+    Ok(match kind {
+        ProofKind::Binary => BinaryProof::from_json_with(&value, &params.g, &params.h)?.serialize(),
+        ProofKind::Schnorr => SchnorrProof::from_json(&value)?.serialize(),
+        ProofKind::Asset => AssetProof::from_json_with(&value, params)?.serialize_public(),
+        ProofKind::Liability => LiabilityProof::from_json_with(&value, params)?.serialize_public(),
+        ProofKind::Solvency => SolvencyProof::from_json_with(&value, params)?.serialize(),
+    })
+}
+
+/// Offsets of the compressed points in the encoding `bytes` of a proof of the given kind, with
+/// liability proofs whose public encoding is `liability_len` bytes
+fn point_offsets(kind: ProofKind, bytes: &[u8], liability_len: usize) -> Vec<usize> {
+    // The points l, a0 and a1 of a binary proof follow its three field elements
+    let binary = |start: usize| vec![start + 96, start + 129, start + 162];
+    match kind {
+        ProofKind::Binary => binary(0),
+        ProofKind::Schnorr | ProofKind::Solvency => vec![32, 65, 98],
+        ProofKind::Asset => {
+            // The secret v sits between the field elements and the balance commitment
+            let balance_comm = if bytes.len() == AssetProof::PUBLIC_ENCODED_LEN {
+                326
+            } else {
+                358
+            };
+            (0..6).map(|i| i * 33).chain(binary(balance_comm)).collect()
+        }
+        ProofKind::Liability => (32..liability_len)
+            .step_by(BinaryProof::ENCODED_LEN)
+            .flat_map(binary)
+            .collect(),
+    }
+}
+
+/// The member `name` of a JSON object
+pub(crate) fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, &'static str> {
+    value.get(name).ok_or("missing field")
+}
+
+/// Decode the member `name` of a JSON object
+pub(crate) fn from_field<T: FromJson>(value: &Value, name: &str) -> Result<T, &'static str> {
+    T::from_json(field(value, name)?)
+}
+
+/// Decode a hex string
+pub(crate) fn from_hex(value: &Value) -> Result<Vec<u8>, &'static str> {
+    let hex = value.as_str().ok_or("expected a hex string")?;
+    hex::decode(hex).map_err(|_| "invalid hex")
+}
+
+/// Decode the hex string member `name` of a JSON object
+pub(crate) fn hex_field(value: &Value, name: &str) -> Result<Vec<u8>, &'static str> {
+    from_hex(field(value, name)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fields::Field256;
+    use crate::secp256k1::{point_mul, Point};
+    use num_bigint::BigUint;
+
+    #[test]
+    fn asset_proof_json_round_trips_to_binary() {
+        let (g, h) = (crate::g(), crate::h());
+        let x = Field256::from(1);
+        let y = point_mul(Point::g(), &x);
+        let proof = AssetProof::create(Some(x), &y, BigUint::from(10u8), &g, &h);
+        let bytes = proof.serialize();
+
+        let json = binary_to_json(ProofKind::Asset, &bytes).unwrap();
+        let public = json_to_binary(ProofKind::Asset, &json).unwrap();
+        assert_eq!(public, proof.serialize_public());
+        assert_eq!(binary_to_json(ProofKind::Asset, &public).unwrap(), json);
+
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["y"], Value::from(hex::encode(y.serialize())));
+        assert!(value.get("v").is_none());
+        assert!(AssetProof::from_json(&value).unwrap().verify());
+    }
+
+    #[test]
+    fn liability_and_solvency_json_round_trip_to_binary() {
+        let (g, h) = (crate::g(), crate::h());
+        let liability = LiabilityProof::create(b"testuser", &BigUint::from(10u8), g, h.clone());
+        let bytes = liability.serialize();
+        let json = binary_to_json(ProofKind::Liability, &bytes).unwrap();
+        let public = json_to_binary(ProofKind::Liability, &json).unwrap();
+        assert_eq!(public, liability.serialize_public());
+        assert_eq!(binary_to_json(ProofKind::Liability, &public).unwrap(), json);
+
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert!(value.get("n").is_none() && value.get("r").is_none());
+
+        let solvency = SolvencyProof::create(&[], &[liability], h);
+        let bytes = solvency.serialize();
+        let json = binary_to_json(ProofKind::Solvency, &bytes).unwrap();
+        assert_eq!(json_to_binary(ProofKind::Solvency, &json).unwrap(), bytes);
+    }
+
+    #[test]
+    fn liability_json_uses_params() {
+        let params = Params::new(b"test").balance_bits(32);
+        let liability =
+            LiabilityProof::create_with_params(b"testuser", &BigUint::from(10u8), &params);
+        let public = liability.serialize_public();
+
+        let json = binary_to_json_with(ProofKind::Liability, &public, &params).unwrap();
+        assert_eq!(
+            json_to_binary_with(ProofKind::Liability, &json, &params).unwrap(),
+            public
+        );
+        let value: Value = serde_json::from_str(&json).unwrap();
+        let read = LiabilityProof::from_json_with(&value, &params).unwrap();
+        assert!(read.check_with(&params).is_ok());
+
+        // 32 bits don't make a proof under the default parameters
+        assert!(binary_to_json(ProofKind::Liability, &public).is_err());
+        assert!(json_to_binary(ProofKind::Liability, &json).is_err());
+    }
+
+    #[test]
+    fn json_rejects_malformed_input() {
+        assert_eq!("asset".parse(), Ok(ProofKind::Asset));
+        assert!("proof".parse::<ProofKind>().is_err());
+        assert!(binary_to_json(ProofKind::Asset, &[0; 10]).is_err());
+        assert!(binary_to_json(ProofKind::Binary, &[5; BinaryProof::ENCODED_LEN]).is_err());
+        assert!(json_to_binary(ProofKind::Schnorr, "{}").is_err());
+        assert!(json_to_binary(ProofKind::Schnorr, "not json").is_err());
+        assert!(Point::from_json(&Value::from("02zz")).is_err());
+        assert!(Point::from_json(&Value::from(hex::encode([5u8; 33]))).is_err());
+    }
+}
@@ -6,23 +6,37 @@ use crate::secp256k1::Point;
 #[macro_use]
 extern crate lazy_static;
 
+#[cfg(feature = "builders")]
+pub mod anonymity_set;
+pub mod archive;
 mod bigint;
+#[cfg(feature = "builders")]
 pub mod builders;
 pub mod data_source;
+pub mod epoch;
 pub mod fields;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod params;
 pub mod proofs;
 pub mod secp256k1;
 pub mod serialization;
+pub mod signer;
 mod util;
+#[cfg(feature = "utxo")]
+pub mod utxo;
+pub mod verifier;
 
 lazy_static! {
-    static ref h_point: Point = Point::from_hash(b"PROVISIONS").unwrap();
+    static ref h_point: Point = Point::from_hash(params::DEFAULT_DOMAIN).unwrap();
 }
 
+/// The generator g of `params::Params::default()`
 pub fn g() -> Point {
     Point::g()
 }
 
+/// The generator h of `params::Params::default()`, hashed from its domain label
 pub fn h() -> Point {
     h_point.clone()
 }
@@ -0,0 +1,166 @@
+use crate::proofs::BALANCE_BITS;
+use crate::secp256k1::Point;
+use crate::serialization::{Deserialize, Serialize};
+
+/// Domain label of the default parameters, which h is hashed from
+pub const DEFAULT_DOMAIN: &[u8] = b"PROVISIONS";
+
+/// Public parameters every proof of a deployment is created and verified with.
+///
+/// h is hashed to the curve from the domain label so nobody knows its discrete log with respect
+/// to g. Deployments and test setups with their own domain label get generators unrelated to
+/// anyone else's, so their proofs can't be mixed up.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Params {
+    pub g: Point,
+    pub h: Point,
+    /// Number of bits liability balances are proven to fit in
+    pub balance_bits: usize,
+    pub domain: Vec<u8>,
+    /// Protocol version the proofs are encoded with
+    pub version: u8,
+}
+
+impl Params {
+    /// Version of the protocol with compact proof encodings. The binary proofs of version 1
+    /// proofs also hold their generators.
+    pub const PROTOCOL_VERSION: u8 = 2;
+
+    /// Parameters for the domain label, which must be at most 255 bytes
+    pub fn new(domain: &[u8]) -> Params {
+        assert!(domain.len() <= 255, "domain label longer than 255 bytes");
+        Params {
+            g: Point::g(),
+            h: Point::from_hash(domain).expect("hash to curve failed"),
+            balance_bits: BALANCE_BITS,
+            domain: domain.to_vec(),
+            version: Params::PROTOCOL_VERSION,
+        }
+    }
+
+    /// Number of bits liability balances are proven to fit in, between 1 and 255
+    pub fn balance_bits(mut self, balance_bits: usize) -> Params {
+        assert!(
+            balance_bits > 0 && balance_bits <= 255,
+            "balance bits must be between 1 and 255"
+        );
+        self.balance_bits = balance_bits;
+        self
+    }
+
+    /// Whether proofs created with `other` verify with these parameters: the generators and
+    /// number of balance bits are the same
+    pub fn matches(&self, other: &Params) -> bool {
+        self.g == other.g && self.h == other.h && self.balance_bits == other.balance_bits
+    }
+
+    /// Length in bytes of the encoding at the start of `bytes`
+    pub(crate) fn encoded_len(bytes: &[u8]) -> usize {
+        69 + bytes[68] as usize
+    }
+
+    /// Read the encoding at the start of `bytes` from an untrusted source, failing if it is
+    /// truncated or its generators aren't the ones of its domain label
+    pub fn read(bytes: &[u8]) -> Result<Params, &'static str> {
+        if bytes.len() < 69 || bytes.len() < Params::encoded_len(bytes) {
+            return Err("truncated parameters");
+        }
+        if bytes[1] == 0 {
+            return Err("balance bits must be positive");
+        }
+        let g = Point::try_deserialize(&bytes[2..35])?;
+        let h = Point::try_deserialize(&bytes[35..68])?;
+
+        let params = Params::deserialize(bytes);
+        if g != params.g || h != params.h {
+            return Err("generators don't match the domain label");
+        }
+        Ok(params)
+    }
+}
+
+impl Default for Params {
+    /// The parameters of `crate::g()` and `crate::h()`
+    fn default() -> Params {
+        Params {
+            g: crate::g(),
+            h: crate::h(),
+            balance_bits: BALANCE_BITS,
+            domain: DEFAULT_DOMAIN.to_vec(),
+            version: Params::PROTOCOL_VERSION,
+        }
+    }
+}
+
+impl Serialize for Params {
+    /// Encodes into 1 + 1 + 33 + 33 + 1 + domain length bytes
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = vec![self.version, self.balance_bits as u8];
+        out.extend(self.g.serialize());
+        out.extend(self.h.serialize());
+        out.push(self.domain.len() as u8);
+        out.extend(&self.domain);
+        out
+    }
+}
+
+impl Deserialize for Params {
+    /// Derives the generators from the domain label rather than taking the encoded ones, so they
+    /// can't be substituted
+    fn deserialize(bytes: &[u8]) -> Params {
+        Params {
+            balance_bits: bytes[1] as usize,
+            version: bytes[0],
+            ..Params::new(&bytes[69..Params::encoded_len(bytes)])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_params_use_default_domain() {
+        assert_eq!(Params::new(DEFAULT_DOMAIN), Params::default());
+        assert_ne!(Params::new(b"test").h, crate::h());
+    }
+
+    #[test]
+    fn params_match_ignoring_version() {
+        let mut legacy = Params::default();
+        legacy.version = 1;
+
+        assert!(legacy.matches(&Params::default()));
+        assert!(!Params::new(b"test").matches(&Params::default()));
+        assert!(!Params::default()
+            .balance_bits(32)
+            .matches(&Params::default()));
+    }
+
+    #[test]
+    fn params_serialization() {
+        let params = Params::new(b"test").balance_bits(32);
+        let bytes = params.serialize();
+
+        assert_eq!(bytes.len(), 73);
+        assert_eq!(Params::encoded_len(&bytes), 73);
+        assert_eq!(Params::deserialize(&bytes), params);
+        assert_eq!(Params::read(&bytes), Ok(params));
+        assert!(Params::read(&bytes[..72]).is_err());
+    }
+
+    #[test]
+    fn params_reject_substituted_generators() {
+        let params = Params::new(b"test");
+        let mut bytes = params.serialize();
+        // Claim the generators of the default domain for the test domain
+        bytes[35..68].copy_from_slice(&crate::h().serialize());
+
+        assert_eq!(
+            Params::read(&bytes),
+            Err("generators don't match the domain label")
+        );
+        assert_eq!(Params::deserialize(&bytes).h, params.h);
+    }
+}
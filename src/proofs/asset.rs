@@ -1,9 +1,20 @@
 use crate::fields::Field256;
+#[cfg(feature = "json")]
+use crate::json::{field, from_field, FromJson, ToJson};
+use crate::params::Params;
 use crate::proofs::binary::BinaryProof;
 use crate::proofs::compute_challenge;
-use crate::secp256k1::{pedersen_commitment, point_mul, point_mul_add, Point};
+use crate::proofs::report::{ensure, Location, VerificationError};
+use crate::secp256k1::{pedersen_commitment, point_mul_add, Point};
 use crate::serialization::{Deserialize, Serialize};
+use crate::signer::Signer;
 use num_bigint::BigUint;
+#[cfg(feature = "json")]
+use serde_json::{json, Value};
+
+pub mod offline;
+
+use self::offline::PendingAssetProof;
 
 #[derive(Debug, PartialEq)]
 pub struct AssetProof {
@@ -29,6 +40,13 @@ pub struct AssetProof {
 }
 
 impl AssetProof {
+    /// Encoded length in bytes
+    pub const ENCODED_LEN: usize = 553;
+    /// Length in bytes of the encoding returned by `serialize_public`
+    pub const PUBLIC_ENCODED_LEN: usize = 521;
+    /// Length in bytes of the version 1 encoding, whose binary proof holds the generators
+    pub const LEGACY_ENCODED_LEN: usize = 619;
+
     pub fn create(
         x: Option<Field256>,
         y: &Point,
@@ -36,58 +54,49 @@ impl AssetProof {
         g: &Point,
         h: &Point,
     ) -> AssetProof {
-        let b = point_mul(g.clone(), &Field256::new(bal));
-        let s = if x.is_some() {
-            Field256::one()
-        } else {
-            Field256::zero()
-        };
+        let pending = PendingAssetProof::prepare(y, bal, x.is_some(), g, h);
         let xhat = x.unwrap_or(Field256::zero());
+        let response = pending.request().respond(&xhat, g, h);
+        pending.assemble(response)
+    }
 
-        // Commitment to balance
-        let v = Field256::rand();
-        let balance_comm = BinaryProof::create(&s, &v, &b, &h);
-
-        // Commitment to private key knowledge
-        let t = Field256::rand();
-        let l = pedersen_commitment(y.clone(), &s, h.clone(), &t);
-
-        let (u1, u2, u3, u4) = (
-            Field256::rand(),
-            Field256::rand(),
-            Field256::rand(),
-            Field256::rand(),
-        );
-        let a1 = pedersen_commitment(b.clone(), &u1, h.clone(), &u2);
-        let a2 = pedersen_commitment(y.clone(), &u1, h.clone(), &u3);
-        let a3 = pedersen_commitment(g.clone(), &u4, h.clone(), &u3);
-
-        let c = &compute_challenge(&[&y, &g, &h, &b, &balance_comm.l, &l, &a1, &a2, &a3]);
-        let rs = &u1 + c * &s;
-        let rv = &u2 + c * &v;
-        let rt = &u3 + c * &t;
-        let rxhat = &u4 + c * xhat;
-
-        AssetProof {
-            g: g.clone(),
-            h: h.clone(),
-            y: y.clone(),
-            b,
-            l,
-            a1,
-            a2,
-            a3,
-            rs,
-            rv,
-            rt,
-            rxhat,
-            v,
-            balance_comm,
+    /// Create a proof for y, claiming it when the signer holds its private key. Only the signer
+    /// handles the private key.
+    pub fn create_with_signer(
+        y: &Point,
+        bal: BigUint,
+        signer: &dyn Signer,
+        g: &Point,
+        h: &Point,
+    ) -> Result<AssetProof, &'static str> {
+        if signer.has_key(y) {
+            AssetProof::create_signed(&[y.clone()], y, bal, signer, g, h)
+        } else {
+            Ok(AssetProof::create(None, y, bal, g, h))
         }
     }
 
+    /// Create a claimed proof for y, the sum of `keys`, with the signer responding for the keys
+    pub(crate) fn create_signed(
+        keys: &[Point],
+        y: &Point,
+        bal: BigUint,
+        signer: &dyn Signer,
+        g: &Point,
+        h: &Point,
+    ) -> Result<AssetProof, &'static str> {
+        let pending = PendingAssetProof::prepare(y, bal, true, g, h);
+        let response = signer.respond(keys, pending.request(), g, h)?;
+        pending.finalize(response)
+    }
+
     /// Verify if the proof is valid or not
     pub fn verify(&self) -> bool {
+        self.check().is_ok()
+    }
+
+    /// Verify the proof, reporting which equation failed
+    pub fn check(&self) -> Result<(), VerificationError> {
         let (g, h, y, b, p, l, a1, a2, a3, rs, rv, rt, rxhat) = (
             self.g.clone(),
             self.h.clone(),
@@ -109,45 +118,55 @@ impl AssetProof {
         let p1 = pedersen_commitment(b, &rs, h.clone(), &rv) == point_mul_add(p, c, &a1);
         let p2 = pedersen_commitment(y, &rs, h.clone(), &rt) == point_mul_add(l.clone(), c, &a2);
         let p3 = pedersen_commitment(g, &rxhat, h, &rt) == point_mul_add(l, c, &a3);
-        let protocol_verified = p1 && p2 && p3;
+        ensure(p1, "protocol 1 equation p1: b^rs * h^rv = p^c * a1")?;
+        ensure(p2, "protocol 1 equation p2: y^rs * h^rt = l^c * a2")?;
+        ensure(p3, "protocol 1 equation p3: g^rxhat * h^rt = l^c * a3")?;
 
         // Protocol 4: Verify binary proof of knowledge of s in [0,1] and v of p
-        let balance_verified = self.balance_comm.verify();
+        let (comm_g, comm_h) = self.balance_comm.generators();
+        ensure(
+            comm_g == &self.b && comm_h == &self.h,
+            "balance commitment is over the generators (b, h)",
+        )?;
+        self.balance_comm
+            .check()
+            .map_err(|e| e.at(Location::BalanceCommitment))
+    }
 
-        protocol_verified && balance_verified
+    /// Verify the proof is over the generators of `params`, rather than the ones it holds,
+    /// reporting which check failed
+    pub fn check_with(&self, params: &Params) -> Result<(), VerificationError> {
+        ensure(
+            self.g == params.g && self.h == params.h,
+            "proof is over the expected generators (g, h)",
+        )?;
+        self.check()
     }
 
     pub fn p_ref(&self) -> &Point {
         &self.balance_comm.l
     }
-}
 
-impl Serialize for AssetProof {
-    /// Encodes into 33 * 6 + 32 * 5 + 261 = 619 bytes
-    fn serialize(&self) -> Vec<u8> {
-        let mut out = vec![];
-        out.extend(self.y.serialize());
-        out.extend(self.b.serialize());
-        out.extend(self.l.serialize());
-        out.extend(self.a1.serialize());
-        out.extend(self.a2.serialize());
-        out.extend(self.a3.serialize());
+    /// Public commitment to the balance of y, b = g^bal
+    pub fn b_ref(&self) -> &Point {
+        &self.b
+    }
 
-        out.extend(self.rs.serialize());
-        out.extend(self.rv.serialize());
-        out.extend(self.rt.serialize());
-        out.extend(self.rxhat.serialize());
-        out.extend(self.v.serialize());
+    /// Read the encoding returned by `serialize` with the parameters it was created with. A
+    /// version 1 encoding only adds the generators of the binary proof at the end.
+    pub fn deserialize_with(bytes: &[u8], params: &Params) -> AssetProof {
+        let mut public = bytes[0..326].to_vec();
+        public.extend(&bytes[358..553]);
 
-        out.extend(self.balance_comm.serialize());
-        out
+        AssetProof {
+            v: Field256::deserialize(&bytes[326..358]),
+            ..AssetProof::deserialize_public_with(&public, params)
+        }
     }
-}
 
-impl Deserialize for AssetProof {
-    fn deserialize(bytes: &[u8]) -> AssetProof {
-        let g = crate::g();
-        let h = crate::h();
+    /// Read the encoding returned by `serialize_public`. The secret v is left zero.
+    pub fn deserialize_public_with(bytes: &[u8], params: &Params) -> AssetProof {
+        let (g, h) = (&params.g, &params.h);
         let y = Point::deserialize(&bytes[0..33]);
         let b = Point::deserialize(&bytes[33..66]);
         let l = Point::deserialize(&bytes[66..99]);
@@ -159,13 +178,13 @@ impl Deserialize for AssetProof {
         let rv = Field256::deserialize(&bytes[230..262]);
         let rt = Field256::deserialize(&bytes[262..294]);
         let rxhat = Field256::deserialize(&bytes[294..326]);
-        let v = Field256::deserialize(&bytes[326..358]);
 
-        let balance_comm = BinaryProof::deserialize(&bytes[358..619]);
+        // The balance commitment is over the generators (b, h)
+        let balance_comm = BinaryProof::deserialize_with(&bytes[326..521], &b, h);
 
         AssetProof {
-            g,
-            h,
+            g: g.clone(),
+            h: h.clone(),
             y,
             b,
             l,
@@ -176,15 +195,109 @@ impl Deserialize for AssetProof {
             rv,
             rt,
             rxhat,
-            v,
+            v: Field256::zero(),
             balance_comm,
         }
     }
+
+    /// Encoding of the public part of the proof, leaving out the secret v. Encodes into
+    /// 33 * 6 + 32 * 4 + 195 = 521 bytes
+    pub fn serialize_public(&self) -> Vec<u8> {
+        let mut out = self.serialize();
+        out.drain(326..358);
+        out
+    }
+}
+
+impl Serialize for AssetProof {
+    /// Encodes into 33 * 6 + 32 * 5 + 195 = 553 bytes
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = vec![];
+        out.extend(self.y.serialize());
+        out.extend(self.b.serialize());
+        out.extend(self.l.serialize());
+        out.extend(self.a1.serialize());
+        out.extend(self.a2.serialize());
+        out.extend(self.a3.serialize());
+
+        out.extend(self.rs.serialize());
+        out.extend(self.rv.serialize());
+        out.extend(self.rt.serialize());
+        out.extend(self.rxhat.serialize());
+        out.extend(self.v.serialize());
+
+        out.extend(self.balance_comm.serialize());
+        out
+    }
+}
+
+#[cfg(feature = "json")]
+impl ToJson for AssetProof {
+    fn to_json(&self) -> Value {
+        json!({
+            "y": self.y.to_json(),
+            "b": self.b.to_json(),
+            "l": self.l.to_json(),
+            "a1": self.a1.to_json(),
+            "a2": self.a2.to_json(),
+            "a3": self.a3.to_json(),
+            "rs": self.rs.to_json(),
+            "rv": self.rv.to_json(),
+            "rt": self.rt.to_json(),
+            "rxhat": self.rxhat.to_json(),
+            "balance_comm": self.balance_comm.to_json(),
+        })
+    }
+}
+
+#[cfg(feature = "json")]
+impl AssetProof {
+    /// Read the public fields of the JSON representation with the parameters the proof was
+    /// created with. The secret v is left zero.
+    pub fn from_json_with(value: &Value, params: &Params) -> Result<AssetProof, &'static str> {
+        let b: Point = from_field(value, "b")?;
+        let balance_comm =
+            BinaryProof::from_json_with(field(value, "balance_comm")?, &b, &params.h)?;
+
+        Ok(AssetProof {
+            g: params.g.clone(),
+            h: params.h.clone(),
+            y: from_field(value, "y")?,
+            b,
+            l: from_field(value, "l")?,
+            a1: from_field(value, "a1")?,
+            a2: from_field(value, "a2")?,
+            a3: from_field(value, "a3")?,
+            rs: from_field(value, "rs")?,
+            rv: from_field(value, "rv")?,
+            rt: from_field(value, "rt")?,
+            rxhat: from_field(value, "rxhat")?,
+            v: Field256::zero(),
+            balance_comm,
+        })
+    }
+}
+
+#[cfg(feature = "json")]
+impl FromJson for AssetProof {
+    /// Reads the public fields over the default parameters
+    fn from_json(value: &Value) -> Result<AssetProof, &'static str> {
+        AssetProof::from_json_with(value, &Params::default())
+    }
+}
+
+impl Deserialize for AssetProof {
+    /// Reads the proof over the default generators, ignoring the generators held by a version 1
+    /// encoding
+    fn deserialize(bytes: &[u8]) -> AssetProof {
+        AssetProof::deserialize_with(bytes, &Params::default())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::secp256k1::point_mul;
 
     #[test]
     fn verify_asset_commitment_with_sk() {
@@ -225,4 +338,35 @@ mod tests {
 
         assert_eq!(proof, proof2);
     }
+
+    #[test]
+    fn asset_proof_reads_legacy_encoding() {
+        let (g, h) = (crate::g(), crate::h());
+        let y = &point_mul(Point::g(), &Field256::from(2));
+        let proof = AssetProof::create(None, y, BigUint::from(5u8), &g, &h);
+
+        // The version 1 encoding ends in the binary proof's generators (b, h)
+        let mut legacy = proof.serialize();
+        legacy.extend(proof.b_ref().serialize());
+        legacy.extend(h.serialize());
+        let params = Params {
+            version: 1,
+            ..Params::default()
+        };
+
+        assert_eq!(legacy.len(), AssetProof::LEGACY_ENCODED_LEN);
+        assert_eq!(AssetProof::deserialize_with(&legacy, &params), proof);
+    }
+
+    #[test]
+    fn asset_proof_rejects_substituted_generators() {
+        let (g, params) = (crate::g(), Params::new(b"test"));
+        let y = &point_mul(Point::g(), &Field256::from(2));
+        let proof = AssetProof::create(None, y, BigUint::from(5u8), &g, &params.h);
+
+        assert!(proof.check().is_ok());
+        assert!(proof.check_with(&params).is_ok());
+        assert!(proof.check_with(&Params::default()).is_err());
+        assert!(!AssetProof::deserialize(&proof.serialize()).verify());
+    }
 }
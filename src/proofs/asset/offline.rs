@@ -0,0 +1,396 @@
+use crate::fields::Field256;
+use crate::params::Params;
+use crate::proofs::binary::BinaryProof;
+use crate::proofs::compute_challenge;
+use crate::proofs::AssetProof;
+use crate::secp256k1::{pedersen_commitment, point_mul, point_mul_add, Point};
+use crate::serialization::{Deserialize, Serialize};
+use num_bigint::BigUint;
+use std::fmt;
+
+/// An asset proof whose private key dependent response has not been computed yet.
+///
+/// This splits `AssetProof::create` so the private key can stay on an air-gapped machine:
+///
+/// 1) Online, `prepare` computes every commitment except a3 and keeps the secrets it used. Its
+///    `request` is written to a file and carried to the offline machine.
+///
+/// 2) Offline, `AssetProofRequest::respond` picks the nonce u4, computes a3 = g^u4 * h^u3, the
+///    challenge c and rxhat = u4 + c * x. Only the response is carried back, u4 never leaves
+///    the offline machine so x can't be recovered from rxhat.
+///
+/// 3) Online, `finalize` recomputes the challenge from a3 and completes the remaining responses.
+///
+/// The pending proof holds the blinding factors of the commitments and must stay private to the
+/// online machine.
+#[derive(PartialEq)]
+pub struct PendingAssetProof {
+    g: Point,
+    h: Point,
+    request: AssetProofRequest,
+
+    s: Field256,
+    v: Field256,
+    t: Field256,
+    u1: Field256,
+    u2: Field256,
+    u3: Field256,
+
+    balance_comm: BinaryProof,
+}
+
+/// Public commitments sent to the machine holding the private key of y.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssetProofRequest {
+    pub y: Point,
+    b: Point,
+    p: Point,
+    l: Point,
+    a1: Point,
+    a2: Point,
+    /// h^u3, the blinding part of a3
+    hu3: Point,
+}
+
+/// The private key dependent part of an asset proof, sent back to the online machine.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssetProofResponse {
+    pub y: Point,
+    a3: Point,
+    rxhat: Field256,
+}
+
+impl fmt::Debug for PendingAssetProof {
+    /// Leaves out the blinding factors so logging a pending proof doesn't reveal them
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PendingAssetProof")
+            .field("g", &self.g)
+            .field("h", &self.h)
+            .field("request", &self.request)
+            .field("balance_comm", &self.balance_comm)
+            .finish()
+    }
+}
+
+impl PendingAssetProof {
+    /// Compute the commitments for a proof over y with balance bal. `owned` is whether the
+    /// exchange holds the private key of y.
+    pub fn prepare(
+        y: &Point,
+        bal: BigUint,
+        owned: bool,
+        g: &Point,
+        h: &Point,
+    ) -> PendingAssetProof {
+        let b = point_mul(g.clone(), &Field256::new(bal));
+        let s = if owned {
+            Field256::one()
+        } else {
+            Field256::zero()
+        };
+
+        // Commitment to balance
+        let v = Field256::rand();
+        let balance_comm = BinaryProof::create(&s, &v, &b, &h);
+
+        // Commitment to private key knowledge
+        let t = Field256::rand();
+        let l = pedersen_commitment(y.clone(), &s, h.clone(), &t);
+
+        let (u1, u2, u3) = (Field256::rand(), Field256::rand(), Field256::rand());
+        let a1 = pedersen_commitment(b.clone(), &u1, h.clone(), &u2);
+        let a2 = pedersen_commitment(y.clone(), &u1, h.clone(), &u3);
+        let hu3 = point_mul(h.clone(), &u3);
+
+        let request = AssetProofRequest {
+            y: y.clone(),
+            b,
+            p: balance_comm.l.clone(),
+            l,
+            a1,
+            a2,
+            hu3,
+        };
+
+        PendingAssetProof {
+            g: g.clone(),
+            h: h.clone(),
+            request,
+            s,
+            v,
+            t,
+            u1,
+            u2,
+            u3,
+            balance_comm,
+        }
+    }
+
+    pub fn request(&self) -> &AssetProofRequest {
+        &self.request
+    }
+
+    /// Complete the proof with the response to its request. Fails if the response is for another
+    /// key or wasn't computed with the private key of y.
+    pub fn finalize(self, response: AssetProofResponse) -> Result<AssetProof, &'static str> {
+        if response.y != self.request.y {
+            return Err("response is for a different key");
+        }
+
+        // g^rxhat * h^rt = l^c * a3
+        let c = &self.request.challenge(&response.a3, &self.g, &self.h);
+        let rt = &self.u3 + c * &self.t;
+        let lhs = pedersen_commitment(self.g.clone(), &response.rxhat, self.h.clone(), &rt);
+        let rhs = point_mul_add(self.request.l.clone(), c, &response.a3);
+        if lhs != rhs {
+            return Err("response was not computed with the private key");
+        }
+
+        Ok(self.assemble(response))
+    }
+
+    /// Complete the proof without checking the response
+    pub(super) fn assemble(self, response: AssetProofResponse) -> AssetProof {
+        let c = &self.request.challenge(&response.a3, &self.g, &self.h);
+        let rs = &self.u1 + c * &self.s;
+        let rv = &self.u2 + c * &self.v;
+        let rt = &self.u3 + c * &self.t;
+        let request = self.request;
+
+        AssetProof {
+            g: self.g,
+            h: self.h,
+            y: request.y,
+            b: request.b,
+            l: request.l,
+            a1: request.a1,
+            a2: request.a2,
+            a3: response.a3,
+            rs,
+            rv,
+            rt,
+            rxhat: response.rxhat,
+            v: self.v,
+            balance_comm: self.balance_comm,
+        }
+    }
+}
+
+impl AssetProofRequest {
+    /// Encoded length in bytes
+    pub const ENCODED_LEN: usize = 231;
+
+    /// Compute the response with xhat, the private key of y when the exchange owns it and zero
+    /// otherwise.
+    pub fn respond(&self, xhat: &Field256, g: &Point, h: &Point) -> AssetProofResponse {
+        let u4 = Field256::rand();
+        // a3 = g^u4 * h^u3
+        let a3 = point_mul_add(g.clone(), &u4, &self.hu3);
+        let c = self.challenge(&a3, g, h);
+        let rxhat = u4 + c * xhat;
+
+        AssetProofResponse {
+            y: self.y.clone(),
+            a3,
+            rxhat,
+        }
+    }
+
+    fn challenge(&self, a3: &Point, g: &Point, h: &Point) -> Field256 {
+        compute_challenge(&[
+            &self.y, g, h, &self.b, &self.p, &self.l, &self.a1, &self.a2, a3,
+        ])
+    }
+}
+
+impl AssetProofResponse {
+    /// Encoded length in bytes
+    pub const ENCODED_LEN: usize = 98;
+}
+
+impl PendingAssetProof {
+    /// Encoded length in bytes
+    pub const ENCODED_LEN: usize = 618;
+}
+
+impl Serialize for AssetProofRequest {
+    /// Encodes into 33 * 7 = 231 bytes
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = vec![];
+        out.extend(self.y.serialize());
+        out.extend(self.b.serialize());
+        out.extend(self.p.serialize());
+        out.extend(self.l.serialize());
+        out.extend(self.a1.serialize());
+        out.extend(self.a2.serialize());
+        out.extend(self.hu3.serialize());
+        out
+    }
+}
+
+impl Deserialize for AssetProofRequest {
+    fn deserialize(bytes: &[u8]) -> AssetProofRequest {
+        AssetProofRequest {
+            y: Point::deserialize(&bytes[0..33]),
+            b: Point::deserialize(&bytes[33..66]),
+            p: Point::deserialize(&bytes[66..99]),
+            l: Point::deserialize(&bytes[99..132]),
+            a1: Point::deserialize(&bytes[132..165]),
+            a2: Point::deserialize(&bytes[165..198]),
+            hu3: Point::deserialize(&bytes[198..231]),
+        }
+    }
+}
+
+impl Serialize for AssetProofResponse {
+    /// Encodes into 33 * 2 + 32 = 98 bytes
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = vec![];
+        out.extend(self.y.serialize());
+        out.extend(self.a3.serialize());
+        out.extend(self.rxhat.serialize());
+        out
+    }
+}
+
+impl Deserialize for AssetProofResponse {
+    fn deserialize(bytes: &[u8]) -> AssetProofResponse {
+        AssetProofResponse {
+            y: Point::deserialize(&bytes[0..33]),
+            a3: Point::deserialize(&bytes[33..66]),
+            rxhat: Field256::deserialize(&bytes[66..98]),
+        }
+    }
+}
+
+impl Serialize for PendingAssetProof {
+    /// Encodes into 231 + 32 * 6 + 195 = 618 bytes
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = self.request.serialize();
+        out.extend(self.s.serialize());
+        out.extend(self.v.serialize());
+        out.extend(self.t.serialize());
+        out.extend(self.u1.serialize());
+        out.extend(self.u2.serialize());
+        out.extend(self.u3.serialize());
+        out.extend(self.balance_comm.serialize());
+        out
+    }
+}
+
+impl PendingAssetProof {
+    /// Read the encoding returned by `serialize` with the parameters the proof was prepared with
+    pub fn deserialize_with(bytes: &[u8], params: &Params) -> PendingAssetProof {
+        let request = AssetProofRequest::deserialize(&bytes[0..231]);
+        // The balance commitment is over the generators (b, h)
+        let balance_comm = BinaryProof::deserialize_with(&bytes[423..618], &request.b, &params.h);
+
+        PendingAssetProof {
+            g: params.g.clone(),
+            h: params.h.clone(),
+            request,
+            s: Field256::deserialize(&bytes[231..263]),
+            v: Field256::deserialize(&bytes[263..295]),
+            t: Field256::deserialize(&bytes[295..327]),
+            u1: Field256::deserialize(&bytes[327..359]),
+            u2: Field256::deserialize(&bytes[359..391]),
+            u3: Field256::deserialize(&bytes[391..423]),
+            balance_comm,
+        }
+    }
+}
+
+impl Deserialize for PendingAssetProof {
+    /// Reads the proof over the default parameters
+    fn deserialize(bytes: &[u8]) -> PendingAssetProof {
+        PendingAssetProof::deserialize_with(bytes, &Params::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::{deserialize_all, serialize_all};
+
+    #[test]
+    fn offline_workflow_produces_valid_proofs() {
+        let (g, h) = (crate::g(), crate::h());
+        let x = Field256::from(42);
+        let y = point_mul(g.clone(), &x);
+        let pending = vec![
+            PendingAssetProof::prepare(&y, BigUint::from(10u8), true, &g, &h),
+            PendingAssetProof::prepare(&y, BigUint::from(20u8), true, &g, &h),
+        ];
+
+        // Online to offline
+        let requests: Vec<AssetProofRequest> =
+            pending.iter().map(|p| p.request().clone()).collect();
+        let request_file = serialize_all(&requests);
+
+        // Offline to online
+        let requests: Vec<AssetProofRequest> =
+            deserialize_all(&request_file, AssetProofRequest::ENCODED_LEN).unwrap();
+        let responses: Vec<AssetProofResponse> = requests
+            .iter()
+            .map(|request| request.respond(&x, &g, &h))
+            .collect();
+        let response_file = serialize_all(&responses);
+
+        let responses: Vec<AssetProofResponse> =
+            deserialize_all(&response_file, AssetProofResponse::ENCODED_LEN).unwrap();
+        for (pending, response) in pending.into_iter().zip(responses.into_iter()) {
+            let proof = pending.finalize(response).unwrap();
+            assert!(proof.verify(), "proof not able to be verified");
+        }
+    }
+
+    #[test]
+    fn finalize_rejects_response_with_wrong_key() {
+        let (g, h) = (crate::g(), crate::h());
+        let y = point_mul(g.clone(), &Field256::from(42));
+        let pending = PendingAssetProof::prepare(&y, BigUint::from(10u8), true, &g, &h);
+
+        let response = pending.request().respond(&Field256::from(43), &g, &h);
+
+        assert!(pending.finalize(response).is_err());
+    }
+
+    #[test]
+    fn pending_asset_proof_serialization() {
+        let (g, h) = (crate::g(), crate::h());
+        let y = point_mul(g.clone(), &Field256::from(42));
+        let pending = PendingAssetProof::prepare(&y, BigUint::from(10u8), false, &g, &h);
+        let pending2 = PendingAssetProof::deserialize(&pending.serialize());
+
+        assert_eq!(pending, pending2);
+
+        let params = Params::new(b"test");
+        let pending = PendingAssetProof::prepare(&y, BigUint::from(10u8), false, &g, &params.h);
+        let pending2 = PendingAssetProof::deserialize_with(&pending.serialize(), &params);
+
+        assert_eq!(pending, pending2);
+        assert_ne!(
+            PendingAssetProof::deserialize(&pending.serialize()),
+            pending
+        );
+    }
+
+    #[test]
+    fn pending_asset_proof_debug_hides_secrets() {
+        let (g, h) = (crate::g(), crate::h());
+        let y = point_mul(g.clone(), &Field256::from(42));
+        let pending = PendingAssetProof::prepare(&y, BigUint::from(10u8), true, &g, &h);
+        let debug = format!("{:?}", pending);
+
+        for secret in &[
+            &pending.v,
+            &pending.t,
+            &pending.u1,
+            &pending.u2,
+            &pending.u3,
+        ] {
+            assert!(!debug.contains(&format!("{:?}", secret.value)));
+        }
+        assert!(debug.contains("request"));
+    }
+}
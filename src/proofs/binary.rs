@@ -1,7 +1,13 @@
 use crate::fields::Field256;
+#[cfg(feature = "json")]
+use crate::json::{from_field, FromJson, ToJson};
+use crate::params::Params;
 use crate::proofs::compute_challenge;
+use crate::proofs::report::{ensure, VerificationError};
 use crate::secp256k1::{pedersen_commitment, point_add, point_inverse, point_mul, Point};
 use crate::serialization::{Deserialize, Serialize};
+#[cfg(feature = "json")]
+use serde_json::{json, Value};
 
 /// Commitment to x given: (g, h, l = g^x*h^y).
 ///
@@ -37,6 +43,11 @@ pub struct BinaryProof {
 }
 
 impl BinaryProof {
+    /// Length in bytes of the encoding returned by `serialize`
+    pub const ENCODED_LEN: usize = 195;
+    /// Length in bytes of the version 1 encoding, which also holds the generators
+    pub const LEGACY_ENCODED_LEN: usize = 261;
+
     /// Create a non-interactive binary commitment to x with the pedersen commitment g^x*h^y
     pub fn create(x: &Field256, y: &Field256, g: &Point, h: &Point) -> BinaryProof {
         if !x.is_binary() {
@@ -72,6 +83,11 @@ impl BinaryProof {
 
     /// Verify if the proof is valid or not
     pub fn verify(&self) -> bool {
+        self.check().is_ok()
+    }
+
+    /// Verify the proof, reporting which equation failed
+    pub fn check(&self) -> Result<(), VerificationError> {
         let c = compute_challenge(&[&self.g, &self.h, &self.l, &self.a0, &self.a1]);
 
         // h^r0 = a0(l)^(c-c1)
@@ -93,12 +109,62 @@ impl BinaryProof {
         );
         let p2 = p2_lhs == p2_rhs;
 
-        return p1 && p2;
+        ensure(p1, "binary proof equation h^r0 = a0 * l^(c - c1)")?;
+        ensure(p2, "binary proof equation h^r1 = a1 * (l / g)^c1")
+    }
+
+    /// Verify the proof is over the expected generators (g, h), rather than the ones it holds,
+    /// reporting which check failed
+    pub fn check_with(&self, g: &Point, h: &Point) -> Result<(), VerificationError> {
+        ensure(
+            &self.g == g && &self.h == h,
+            "binary proof is over the expected generators",
+        )?;
+        self.check()
+    }
+
+    /// The generators (g, h) the commitment l is over
+    pub(crate) fn generators(&self) -> (&Point, &Point) {
+        (&self.g, &self.h)
+    }
+
+    /// Length in bytes of a binary proof encoded with protocol `version`
+    pub fn encoded_len(version: u8) -> usize {
+        if version < Params::PROTOCOL_VERSION {
+            BinaryProof::LEGACY_ENCODED_LEN
+        } else {
+            BinaryProof::ENCODED_LEN
+        }
+    }
+
+    /// Read the compact encoding returned by `serialize`, or the start of a version 1 encoding,
+    /// with the generators it was created with
+    pub fn deserialize_with(bytes: &[u8], g: &Point, h: &Point) -> BinaryProof {
+        BinaryProof {
+            g: g.clone(),
+            h: h.clone(),
+            l: Point::deserialize(&bytes[96..129]),
+            a0: Point::deserialize(&bytes[129..162]),
+            a1: Point::deserialize(&bytes[162..195]),
+            c1: Field256::deserialize(&bytes[0..32]),
+            r0: Field256::deserialize(&bytes[32..64]),
+            r1: Field256::deserialize(&bytes[64..96]),
+        }
+    }
+
+    /// The version 1 encoding, the compact one followed by g and h
+    #[cfg(test)]
+    pub(crate) fn serialize_legacy(&self) -> Vec<u8> {
+        let mut out = self.serialize();
+        out.extend(self.g.serialize());
+        out.extend(self.h.serialize());
+        out
     }
 }
 
 impl Serialize for BinaryProof {
-    /// Encodes into 32 * 3 + 33 * 5 = 261 bytes
+    /// Encodes into 32 * 3 + 33 * 3 = 195 bytes. The generators are left out, the reader knows
+    /// them from the proof the binary proof is part of.
     fn serialize(&self) -> Vec<u8> {
         let mut out = vec![];
         out.extend(self.c1.serialize());
@@ -107,32 +173,58 @@ impl Serialize for BinaryProof {
         out.extend(self.l.serialize());
         out.extend(self.a0.serialize());
         out.extend(self.a1.serialize());
-        out.extend(self.g.serialize());
-        out.extend(self.h.serialize());
         out
     }
 }
 
+#[cfg(feature = "json")]
+impl ToJson for BinaryProof {
+    fn to_json(&self) -> Value {
+        json!({
+            "c1": self.c1.to_json(),
+            "r0": self.r0.to_json(),
+            "r1": self.r1.to_json(),
+            "l": self.l.to_json(),
+            "a0": self.a0.to_json(),
+            "a1": self.a1.to_json(),
+        })
+    }
+}
+
+#[cfg(feature = "json")]
+impl BinaryProof {
+    /// Read the JSON representation with the generators it was created with
+    pub(crate) fn from_json_with(
+        value: &Value,
+        g: &Point,
+        h: &Point,
+    ) -> Result<BinaryProof, &'static str> {
+        Ok(BinaryProof {
+            g: g.clone(),
+            h: h.clone(),
+            l: from_field(value, "l")?,
+            a0: from_field(value, "a0")?,
+            a1: from_field(value, "a1")?,
+            c1: from_field(value, "c1")?,
+            r0: from_field(value, "r0")?,
+            r1: from_field(value, "r1")?,
+        })
+    }
+}
+
+#[cfg(feature = "json")]
+impl FromJson for BinaryProof {
+    /// Reads the proof over the default generators
+    fn from_json(value: &Value) -> Result<BinaryProof, &'static str> {
+        BinaryProof::from_json_with(value, &crate::g(), &crate::h())
+    }
+}
+
 impl Deserialize for BinaryProof {
+    /// Reads the proof over the default generators. The generators held by a version 1 encoding
+    /// are ignored so they can't be substituted.
     fn deserialize(bytes: &[u8]) -> BinaryProof {
-        let c1 = Field256::deserialize(&bytes[0..32]);
-        let r0 = Field256::deserialize(&bytes[32..64]);
-        let r1 = Field256::deserialize(&bytes[64..96]);
-        let l = Point::deserialize(&bytes[96..129]);
-        let a0 = Point::deserialize(&bytes[129..162]);
-        let a1 = Point::deserialize(&bytes[162..195]);
-        let g = Point::deserialize(&bytes[195..228]);
-        let h = Point::deserialize(&bytes[228..261]);
-        BinaryProof {
-            g,
-            h,
-            l,
-            a0,
-            a1,
-            c1,
-            r0,
-            r1,
-        }
+        BinaryProof::deserialize_with(bytes, &crate::g(), &crate::h())
     }
 }
 
@@ -187,4 +279,34 @@ mod tests {
 
         assert_eq!(proof, proof2);
     }
+
+    #[test]
+    fn binary_proof_reads_legacy_encoding() {
+        let g = crate::g();
+        let h = crate::h();
+        let y = &Field256::rand();
+
+        let proof = BinaryProof::create(&Field256::from(1), y, &g, &h);
+        let bytes = proof.serialize_legacy();
+
+        assert_eq!(proof.serialize().len(), BinaryProof::encoded_len(2));
+        assert_eq!(bytes.len(), BinaryProof::encoded_len(1));
+        assert_eq!(BinaryProof::deserialize(&bytes), proof);
+    }
+
+    #[test]
+    fn binary_proof_rejects_substituted_generators() {
+        let (g, h) = (crate::g(), crate::h());
+        let y = &Field256::rand();
+
+        // Any commitment l * h^y opens to 1 over the generators (l, h)
+        let l = point_mul(g.clone(), &Field256::from(5));
+        let forged = BinaryProof::create(&Field256::from(1), y, &l, &h);
+        assert!(forged.verify());
+        assert!(forged.check_with(&g, &h).is_err());
+        assert!(!BinaryProof::deserialize(&forged.serialize_legacy()).verify());
+
+        let proof = BinaryProof::create(&Field256::from(1), y, &g, &h);
+        assert!(proof.check_with(&g, &h).is_ok());
+    }
 }
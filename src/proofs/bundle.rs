@@ -0,0 +1,360 @@
+use crate::epoch::Epoch;
+use crate::params::Params;
+use crate::proofs::report::{ensure, Location, VerificationError, VerificationReport};
+use crate::proofs::{
+    AssetProof, CommitmentNode, CommitmentTree, LiabilityProof, LiabilityTree, MultisigAssetProof,
+    SolvencyProof,
+};
+use crate::serialization::{Deserialize, Serialize};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Everything published alongside the individual asset and liability proofs: the proof of
+/// solvency, the root of the Merkle tree over the liability proofs it was computed from and the
+/// root of the commitment tree summing their balance commitments.
+pub struct SolvencyBundle {
+    /// Publication the bundle belongs to
+    pub epoch: Epoch,
+    /// Parameters every proof of the publication was created with
+    pub params: Params,
+    pub solvency: SolvencyProof,
+    /// Root of the `LiabilityTree` over the liability proofs summed into `solvency`
+    pub liability_root: [u8; 32],
+    /// Root of the `CommitmentTree` over the same liability proofs. Its commitment is the total
+    /// liability commitment used by `solvency`.
+    pub commitment_root: CommitmentNode,
+}
+
+impl SolvencyBundle {
+    pub fn create(
+        epoch: Epoch,
+        asset_proofs: &[AssetProof],
+        multisig_proofs: &[MultisigAssetProof],
+        liability_proofs: &[LiabilityProof],
+        params: Params,
+    ) -> Result<SolvencyBundle, &'static str> {
+        Ok(SolvencyBundle {
+            epoch,
+            solvency: SolvencyProof::create_with_multisig(
+                asset_proofs,
+                multisig_proofs,
+                liability_proofs,
+                params.h.clone(),
+            ),
+            params,
+            liability_root: LiabilityTree::new(liability_proofs).root(),
+            commitment_root: CommitmentTree::new(liability_proofs)?.root().clone(),
+        })
+    }
+
+    /// Verify the bundle is over the expected parameters, its proof of solvency and that the
+    /// published liability proofs are exactly the ones committed to by the roots
+    pub fn verify(&self, params: &Params, liability_proofs: &[LiabilityProof]) -> bool {
+        let commitment_root = match CommitmentTree::new(liability_proofs) {
+            Ok(tree) => tree.root().clone(),
+            Err(_) => return false,
+        };
+
+        self.params.matches(params)
+            && self.solvency.check_with(params).is_ok()
+            && LiabilityTree::new(liability_proofs).root() == self.liability_root
+            && commitment_root == self.commitment_root
+    }
+
+    /// Verify the proof of solvency, over the expected parameters, was computed from the given
+    /// asset proofs and the total liability commitment at the root of the commitment tree.
+    /// Together with a customer's `CommitmentPath` this shows their balance was counted without
+    /// downloading the other liability proofs.
+    pub fn verify_assets(
+        &self,
+        params: &Params,
+        asset_proofs: &[AssetProof],
+        multisig_proofs: &[MultisigAssetProof],
+    ) -> bool {
+        self.params.matches(params)
+            && self.solvency.check_with(params).is_ok()
+            && self.solvency.verify_commitments(
+                asset_proofs,
+                multisig_proofs,
+                &self.commitment_root.commitment,
+            )
+    }
+
+    /// Verify the bundle together with every proof it was computed from over the default
+    /// parameters, reporting each failed check rather than stopping at the first one
+    pub fn check(
+        &self,
+        asset_proofs: &[AssetProof],
+        multisig_proofs: &[MultisigAssetProof],
+        liability_proofs: &[LiabilityProof],
+    ) -> VerificationReport {
+        self.check_with(
+            &Params::default(),
+            asset_proofs,
+            multisig_proofs,
+            liability_proofs,
+        )
+    }
+
+    /// Verify the bundle together with every proof it was computed from over the expected
+    /// parameters, rather than the ones they hold, reporting each failed check
+    pub fn check_with(
+        &self,
+        params: &Params,
+        asset_proofs: &[AssetProof],
+        multisig_proofs: &[MultisigAssetProof],
+        liability_proofs: &[LiabilityProof],
+    ) -> VerificationReport {
+        let mut report = VerificationReport::default();
+        report.record(ensure(
+            self.params.matches(params),
+            "bundle parameters are the expected ones",
+        ));
+
+        let assets = check_each(
+            asset_proofs,
+            |proof| proof.check_with(params),
+            Location::Asset,
+        );
+        let multisigs = check_each(
+            multisig_proofs,
+            |proof| proof.check_with(params),
+            Location::MultisigAsset,
+        );
+        let liabilities = check_each(
+            liability_proofs,
+            |proof| proof.check_with(params),
+            Location::Liability,
+        );
+        for result in assets.into_iter().chain(multisigs).chain(liabilities) {
+            report.record(result);
+        }
+
+        report.record(
+            self.solvency
+                .check_with(params)
+                .and_then(|_| {
+                    ensure(
+                        self.solvency.verify_commitments(
+                            asset_proofs,
+                            multisig_proofs,
+                            &self.commitment_root.commitment,
+                        ),
+                        "proven commitment is the asset commitments less the liability commitment",
+                    )
+                })
+                .map_err(|e| e.at(Location::Solvency)),
+        );
+        report.record(ensure(
+            LiabilityTree::new(liability_proofs).root() == self.liability_root,
+            "liability root commits to the published liability proofs",
+        ));
+        report.record(match CommitmentTree::new(liability_proofs) {
+            Ok(tree) => ensure(
+                *tree.root() == self.commitment_root,
+                "commitment root sums the published liability proofs",
+            ),
+            Err(_) => Err(VerificationError::new(
+                "commitment root sums the published liability proofs",
+            )),
+        });
+
+        report
+    }
+}
+
+/// Check every proof, nesting failures in the location of the proof's index
+#[cfg(feature = "rayon")]
+fn check_each<T, C, L>(proofs: &[T], check: C, location: L) -> Vec<Result<(), VerificationError>>
+where
+    T: Sync,
+    C: Fn(&T) -> Result<(), VerificationError> + Sync,
+    L: Fn(usize) -> Location + Sync,
+{
+    proofs
+        .par_iter()
+        .enumerate()
+        .map(|(i, proof)| check(proof).map_err(|e| e.at(location(i))))
+        .collect()
+}
+
+/// Check every proof on the calling thread, for builds without rayon
+#[cfg(not(feature = "rayon"))]
+fn check_each<T, C, L>(proofs: &[T], check: C, location: L) -> Vec<Result<(), VerificationError>>
+where
+    C: Fn(&T) -> Result<(), VerificationError>,
+    L: Fn(usize) -> Location,
+{
+    proofs
+        .iter()
+        .enumerate()
+        .map(|(i, proof)| check(proof).map_err(|e| e.at(location(i))))
+        .collect()
+}
+
+impl Serialize for SolvencyBundle {
+    /// Encodes into 24 + params length + 32 + 65 + 131 bytes, 331 with the default parameters
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = self.epoch.serialize();
+        out.extend(self.params.serialize());
+        out.extend(&self.liability_root);
+        out.extend(self.commitment_root.serialize());
+        out.extend(self.solvency.serialize());
+        out
+    }
+}
+
+impl SolvencyBundle {
+    /// Read a bundle from an untrusted source, failing if it is truncated or its parameters
+    /// don't match their domain label
+    pub fn read(bytes: &[u8]) -> Result<SolvencyBundle, &'static str> {
+        let params = Params::read(bytes.get(24..).ok_or("truncated bundle")?)?;
+        let start = 24 + Params::encoded_len(&bytes[24..]);
+        if bytes.len() != start + 32 + 65 + 131 {
+            return Err("unexpected bundle length");
+        }
+        Ok(SolvencyBundle::with_params(bytes, params))
+    }
+
+    /// Read the encoding `bytes`, whose parameters have already been read as `params`
+    fn with_params(bytes: &[u8], params: Params) -> SolvencyBundle {
+        let start = 24 + Params::encoded_len(&bytes[24..]);
+        let mut liability_root = [0; 32];
+        liability_root.copy_from_slice(&bytes[start..(start + 32)]);
+        let solvency = SolvencyProof::deserialize_with(&bytes[(start + 97)..], &params);
+
+        SolvencyBundle {
+            epoch: Epoch::deserialize(&bytes[0..24]),
+            params,
+            solvency,
+            liability_root,
+            commitment_root: CommitmentNode::deserialize(&bytes[(start + 32)..(start + 97)]),
+        }
+    }
+}
+
+impl Deserialize for SolvencyBundle {
+    fn deserialize(bytes: &[u8]) -> SolvencyBundle {
+        SolvencyBundle::with_params(bytes, Params::deserialize(&bytes[24..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fields::Field256;
+    use crate::secp256k1::{point_mul, Point};
+    use num_bigint::BigUint;
+
+    #[test]
+    fn bundle_commits_to_liability_proofs() {
+        let (g, h) = (crate::g(), crate::h());
+        let x = Field256::from(1);
+        let y = point_mul(Point::g(), &x);
+        let assets = vec![AssetProof::create(Some(x), &y, BigUint::from(30u8), &g, &h)];
+        let liabilities: Vec<LiabilityProof> = (0..3u8)
+            .map(|i| {
+                let id = format!("user{}", i);
+                LiabilityProof::create(id.as_bytes(), &BigUint::from(10u8), g.clone(), h.clone())
+            })
+            .collect();
+
+        let bundle = SolvencyBundle::create(
+            Epoch::new(1, 1_560_000_000, 580_000),
+            &assets,
+            &[],
+            &liabilities,
+            Params::default(),
+        )
+        .unwrap();
+        let bundle = SolvencyBundle::deserialize(&bundle.serialize());
+
+        assert_eq!(bundle.params, Params::default());
+        let params = Params::default();
+        assert!(bundle.verify(&params, &liabilities));
+        assert!(!bundle.verify(&params, &liabilities[1..]));
+        assert!(bundle.verify_assets(&params, &assets, &[]));
+
+        let tree = LiabilityTree::new(&liabilities);
+        let inclusion = tree.prove(liabilities[0].cid()).unwrap();
+        assert!(liabilities[0].verify_as_customer_with_inclusion(
+            b"user0",
+            &BigUint::from(10u8),
+            &inclusion,
+            &bundle.liability_root
+        ));
+
+        let tree = CommitmentTree::new(&liabilities).unwrap();
+        let path = tree.prove(liabilities[1].cid()).unwrap();
+        assert!(liabilities[1].verify_as_customer_with_path(
+            b"user1",
+            &BigUint::from(10u8),
+            &path,
+            &bundle.commitment_root
+        ));
+    }
+
+    #[test]
+    fn bundle_check_rejects_unexpected_params() {
+        let params = Params::new(b"test");
+        let (g, h) = (params.g.clone(), params.h.clone());
+        let x = Field256::from(1);
+        let y = point_mul(Point::g(), &x);
+        let assets = vec![AssetProof::create(Some(x), &y, BigUint::from(10u8), &g, &h)];
+        let liabilities = vec![LiabilityProof::create(b"user", &BigUint::from(10u8), g, h)];
+        let epoch = Epoch::new(1, 1_560_000_000, 580_000);
+        let bundle = SolvencyBundle::create(epoch, &assets, &[], &liabilities, params.clone());
+        let mut bytes = bundle.unwrap().serialize();
+        let bundle = SolvencyBundle::read(&bytes).unwrap();
+
+        // The generator h recorded in the header must be the one of its domain label
+        bytes[(24 + 35)..(24 + 68)].copy_from_slice(&crate::h().serialize());
+        assert!(SolvencyBundle::read(&bytes).is_err());
+        assert!(SolvencyBundle::read(&bytes[..100]).is_err());
+
+        assert!(bundle
+            .check_with(&params, &assets, &[], &liabilities)
+            .is_ok());
+        let report = bundle.check(&assets, &[], &liabilities);
+        assert_eq!(
+            report.failures[0],
+            VerificationError::new("bundle parameters are the expected ones")
+        );
+        assert!(bundle.verify(&params, &liabilities));
+        assert!(!bundle.verify(&Params::default(), &liabilities));
+        assert!(!bundle.verify_assets(&Params::default(), &assets, &[]));
+    }
+
+    #[test]
+    fn bundle_check_reports_failed_checks() {
+        let (g, h) = (crate::g(), crate::h());
+        let x = Field256::from(1);
+        let y = point_mul(Point::g(), &x);
+        let assets = vec![AssetProof::create(Some(x), &y, BigUint::from(20u8), &g, &h)];
+        let mut liabilities: Vec<LiabilityProof> = (0..2u8)
+            .map(|i| {
+                let id = format!("user{}", i);
+                LiabilityProof::create(id.as_bytes(), &BigUint::from(10u8), g.clone(), h.clone())
+            })
+            .collect();
+        let epoch = Epoch::new(1, 1_560_000_000, 580_000);
+        let bundle =
+            SolvencyBundle::create(epoch, &assets, &[], &liabilities, Params::default()).unwrap();
+
+        assert!(bundle.check(&assets, &[], &liabilities).is_ok());
+
+        // Corrupt r1 of the binary proof of bit 17 of the second liability
+        let mut bytes = liabilities[1].serialize();
+        bytes[32 + 195 * 17 + 95] ^= 1;
+        liabilities[1] = LiabilityProof::deserialize(&bytes);
+        let report = bundle.check(&assets, &[], &liabilities);
+
+        assert_eq!(report.failures.len(), 2);
+        assert_eq!(
+            report.failures[0],
+            VerificationError::new("binary proof equation h^r1 = a1 * (l / g)^c1")
+                .at(Location::Bit(17))
+                .at(Location::Liability(1))
+        );
+    }
+}
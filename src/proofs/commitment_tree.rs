@@ -0,0 +1,214 @@
+use crate::proofs::merkle::{
+    build_levels, path_root, siblings, to_array, LEAF_PREFIX, NODE_PREFIX,
+};
+use crate::proofs::LiabilityProof;
+use crate::secp256k1::{point_add, Point};
+use crate::serialization::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Node of a `CommitmentTree`: the sum of the balance commitments below it and a hash binding
+/// the sum to the customer identifiers it was computed from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommitmentNode {
+    pub commitment: Point,
+    pub hash: [u8; 32],
+}
+
+impl CommitmentNode {
+    fn leaf(cid: &[u8; 32], z: Point) -> CommitmentNode {
+        let mut hasher = Sha256::new();
+        hasher.input(&[LEAF_PREFIX]);
+        hasher.input(&cid[..]);
+        hasher.input(&z.serialize());
+        CommitmentNode {
+            commitment: z,
+            hash: to_array(hasher.result().as_slice()),
+        }
+    }
+
+    fn parent(left: &CommitmentNode, right: &CommitmentNode) -> CommitmentNode {
+        let commitment = point_add(left.commitment.clone(), &right.commitment);
+        let mut hasher = Sha256::new();
+        hasher.input(&[NODE_PREFIX]);
+        hasher.input(&left.hash);
+        hasher.input(&right.hash);
+        hasher.input(&commitment.serialize());
+        CommitmentNode {
+            commitment,
+            hash: to_array(hasher.result().as_slice()),
+        }
+    }
+}
+
+impl Serialize for CommitmentNode {
+    /// Encodes into 33 + 32 = 65 bytes
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = self.commitment.serialize();
+        out.extend(&self.hash);
+        out
+    }
+}
+
+impl Deserialize for CommitmentNode {
+    fn deserialize(bytes: &[u8]) -> CommitmentNode {
+        CommitmentNode {
+            commitment: Point::deserialize(&bytes[0..33]),
+            hash: to_array(&bytes[33..65]),
+        }
+    }
+}
+
+/// Summation tree over the balance commitments `z` of a set of liability proofs.
+///
+/// Every internal node holds the sum of its children's commitments, so the root commitment is
+/// the total liability commitment used by the `SolvencyProof`. A customer can check their
+/// commitment was counted in the total from a logarithmic path of sibling nodes, without
+/// downloading the other customers' proofs. Leaves are ordered by customer identifier and a
+/// node without a sibling is carried up unchanged, like the `LiabilityTree`.
+pub struct CommitmentTree {
+    cids: Vec<[u8; 32]>,
+    levels: Vec<Vec<CommitmentNode>>,
+}
+
+impl CommitmentTree {
+    pub fn new(proofs: &[LiabilityProof]) -> Result<CommitmentTree, &'static str> {
+        if proofs.is_empty() {
+            return Err("commitment tree needs at least one liability proof");
+        }
+
+        let mut leaves: Vec<([u8; 32], CommitmentNode)> = proofs
+            .iter()
+            .map(|proof| (*proof.cid(), CommitmentNode::leaf(proof.cid(), proof.z())))
+            .collect();
+        leaves.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let cids = leaves.iter().map(|leaf| leaf.0).collect();
+        let nodes = leaves.into_iter().map(|leaf| leaf.1).collect();
+        let levels = build_levels(nodes, |left, right| {
+            Some(CommitmentNode::parent(left, right))
+        })
+        .expect("summing never fails");
+
+        Ok(CommitmentTree { cids, levels })
+    }
+
+    pub fn root(&self) -> &CommitmentNode {
+        &self.levels[self.levels.len() - 1][0]
+    }
+
+    /// Path from the leaf of customer identifier `cid` to the root
+    pub fn prove(&self, cid: &[u8; 32]) -> Option<CommitmentPath> {
+        let index = self.cids.binary_search(cid).ok()?;
+
+        Some(CommitmentPath {
+            index,
+            leaves: self.cids.len(),
+            siblings: siblings(&self.levels, index),
+        })
+    }
+}
+
+/// Sibling nodes on the way from a leaf of a `CommitmentTree` to its root
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommitmentPath {
+    /// Position of the leaf in the tree
+    pub index: usize,
+    /// Number of leaves in the tree
+    pub leaves: usize,
+    /// Sibling nodes from the leaf up, skipping levels where the node has no sibling
+    siblings: Vec<CommitmentNode>,
+}
+
+impl CommitmentPath {
+    /// Verify that the commitment `z` of customer identifier `cid` is summed into `root`
+    pub fn verify(&self, cid: &[u8; 32], z: &Point, root: &CommitmentNode) -> bool {
+        let computed = path_root(
+            CommitmentNode::leaf(cid, z.clone()),
+            self.index,
+            self.leaves,
+            &self.siblings,
+            |left, right| Some(CommitmentNode::parent(left, right)),
+        );
+        computed.as_ref() == Some(root)
+    }
+}
+
+impl Serialize for CommitmentPath {
+    /// Encodes into 8 + 8 + 65 * path length bytes
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = vec![];
+        out.extend(&(self.index as u64).to_be_bytes());
+        out.extend(&(self.leaves as u64).to_be_bytes());
+        for sibling in self.siblings.iter() {
+            out.extend(sibling.serialize());
+        }
+        out
+    }
+}
+
+impl Deserialize for CommitmentPath {
+    fn deserialize(bytes: &[u8]) -> CommitmentPath {
+        let mut index = [0; 8];
+        index.copy_from_slice(&bytes[0..8]);
+        let mut leaves = [0; 8];
+        leaves.copy_from_slice(&bytes[8..16]);
+
+        CommitmentPath {
+            index: u64::from_be_bytes(index) as usize,
+            leaves: u64::from_be_bytes(leaves) as usize,
+            siblings: bytes[16..]
+                .chunks(65)
+                .map(CommitmentNode::deserialize)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secp256k1::point_sum;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn root_commitment_is_sum_of_liabilities() {
+        let proofs = gen_proofs(5);
+        let tree = CommitmentTree::new(&proofs).unwrap();
+
+        let zs: Vec<Point> = proofs.iter().map(|proof| proof.z()).collect();
+        let zs: Vec<&Point> = zs.iter().collect();
+        assert_eq!(tree.root().commitment, point_sum(&zs));
+    }
+
+    #[test]
+    fn commitment_paths_verify_for_every_leaf() {
+        let proofs = gen_proofs(5);
+        let tree = CommitmentTree::new(&proofs).unwrap();
+
+        for proof in proofs.iter() {
+            let path = tree.prove(proof.cid()).unwrap();
+            let path = CommitmentPath::deserialize(&path.serialize());
+            assert!(path.verify(proof.cid(), &proof.z(), tree.root()));
+        }
+    }
+
+    #[test]
+    fn commitment_path_rejects_other_commitments() {
+        let proofs = gen_proofs(3);
+        let tree = CommitmentTree::new(&proofs).unwrap();
+        let path = tree.prove(proofs[0].cid()).unwrap();
+
+        assert!(!path.verify(proofs[0].cid(), &proofs[1].z(), tree.root()));
+        assert!(!path.verify(proofs[1].cid(), &proofs[0].z(), tree.root()));
+    }
+
+    fn gen_proofs(num: u8) -> Vec<LiabilityProof> {
+        (0..num)
+            .map(|i| {
+                let id = format!("user{}", i);
+                let (g, h) = (crate::g(), crate::h());
+                LiabilityProof::create(id.as_bytes(), &BigUint::from(i + 1), g, h)
+            })
+            .collect()
+    }
+}
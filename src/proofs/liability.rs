@@ -1,12 +1,21 @@
 use crate::bigint::{biguint_to_bits_le, biguint_to_bytes_be};
 use crate::fields::Field256;
+#[cfg(feature = "json")]
+use crate::json::{field, hex_field, FromJson, ToJson};
+use crate::params::Params;
 use crate::proofs::binary::BinaryProof;
+use crate::proofs::commitment_tree::{CommitmentNode, CommitmentPath};
+use crate::proofs::merkle::InclusionProof;
+use crate::proofs::report::{ensure, Location, VerificationError};
 use crate::secp256k1::{pedersen_commitment, point_mul, Point};
 use crate::serialization::{Deserialize, Serialize};
 use num_bigint::BigUint;
 use num_traits::identities::Zero;
 use num_traits::pow::Pow;
+#[cfg(feature = "rayon")]
 use rayon::prelude::*;
+#[cfg(feature = "json")]
+use serde_json::{json, Value};
 use sha2::{Digest, Sha256};
 
 pub struct LiabilityProof {
@@ -28,9 +37,10 @@ pub struct LiabilityProof {
     pub r: BigUint,
 }
 
-const BALANCE_BITS: usize = 51;
+/// Number of bits balances are proven to fit in by the default parameters
+pub(crate) const BALANCE_BITS: usize = 51;
 
-fn compute_cid(identifier: &[u8], n: &BigUint) -> [u8; 32] {
+pub(super) fn compute_cid(identifier: &[u8], n: &BigUint) -> [u8; 32] {
     let mut data = identifier.to_vec();
     data.extend(n.to_bytes_be());
     let digest = Sha256::digest(&data);
@@ -39,27 +49,79 @@ fn compute_cid(identifier: &[u8], n: &BigUint) -> [u8; 32] {
     out
 }
 
+/// Prove each bit, returning the proofs and the sum of their blinding factors weighted by the
+/// bit position
+#[cfg(feature = "rayon")]
+fn prove_bits(bits: &[u8], g: &Point, h: &Point) -> (BigUint, Vec<BinaryProof>) {
+    let initial_value = (BigUint::zero(), Vec::with_capacity(bits.len()));
+    bits.par_iter()
+        .enumerate()
+        .fold_with(initial_value, |mut acc, (i, bit)| {
+            let r_i = Field256::rand();
+            let comm = BinaryProof::create(&Field256::from(*bit), &r_i, g, h);
+            acc.0 += &r_i.value << i;
+            acc.1.push(comm);
+            acc
+        })
+        .reduce_with(|mut acc, (partial_total, bits)| {
+            acc.0 += partial_total;
+            acc.1.extend(bits);
+            acc
+        })
+        .unwrap()
+}
+
+/// Prove each bit on the calling thread, for builds without rayon
+#[cfg(not(feature = "rayon"))]
+fn prove_bits(bits: &[u8], g: &Point, h: &Point) -> (BigUint, Vec<BinaryProof>) {
+    let mut r = BigUint::zero();
+    let mut proofs = Vec::with_capacity(bits.len());
+    for (i, bit) in bits.iter().enumerate() {
+        let r_i = Field256::rand();
+        proofs.push(BinaryProof::create(&Field256::from(*bit), &r_i, g, h));
+        r += &r_i.value << i;
+    }
+    (r, proofs)
+}
+
 impl LiabilityProof {
+    /// Length in bytes of the encoding returned by `serialize_public`
+    pub const PUBLIC_ENCODED_LEN: usize = 32 + BinaryProof::ENCODED_LEN * BALANCE_BITS;
+
+    /// Length in bytes of the public encoding of proofs created with `params`
+    pub fn public_encoded_len(params: &Params) -> usize {
+        32 + BinaryProof::ENCODED_LEN * params.balance_bits
+    }
+
     pub fn create(identifier: &[u8], balance: &BigUint, g: Point, h: Point) -> LiabilityProof {
-        let bits = biguint_to_bits_le(balance, BALANCE_BITS);
-
-        let initial_value = (BigUint::zero(), Vec::with_capacity(bits.len()));
-        let (r, bit_proofs): (BigUint, Vec<BinaryProof>) = bits
-            .par_iter()
-            .enumerate()
-            .fold_with(initial_value, |mut acc, (i, bit)| {
-                let r_i = Field256::rand();
-                let comm = BinaryProof::create(&Field256::from(*bit), &r_i, &g, &h);
-                acc.0 += &r_i.value << i;
-                acc.1.push(comm);
-                acc
-            })
-            .reduce_with(|mut acc, (partial_total, bits)| {
-                acc.0 += partial_total;
-                acc.1.extend(bits);
-                acc
-            })
-            .unwrap();
+        LiabilityProof::create_with_bits(identifier, balance, BALANCE_BITS, g, h)
+    }
+
+    /// Create a proof over the generators of `params` with a binary proof for each of its balance
+    /// bits
+    pub fn create_with_params(
+        identifier: &[u8],
+        balance: &BigUint,
+        params: &Params,
+    ) -> LiabilityProof {
+        LiabilityProof::create_with_bits(
+            identifier,
+            balance,
+            params.balance_bits,
+            params.g.clone(),
+            params.h.clone(),
+        )
+    }
+
+    fn create_with_bits(
+        identifier: &[u8],
+        balance: &BigUint,
+        balance_bits: usize,
+        g: Point,
+        h: Point,
+    ) -> LiabilityProof {
+        let bits = biguint_to_bits_le(balance, balance_bits);
+        let (r, bit_proofs) = prove_bits(&bits, &g, &h);
 
         let n = Field256::rand().value;
         let cid = compute_cid(identifier, &n);
@@ -76,9 +138,35 @@ impl LiabilityProof {
 
     /// Verify that all the binary proofd are proven.
     pub fn verify(&self) -> bool {
+        self.check().is_ok()
+    }
+
+    /// Verify all the binary proofs, reporting the first bit that fails
+    pub fn check(&self) -> Result<(), VerificationError> {
         // For the public verification, we simply verify that all the binary proofs are
         // correct. The customer will verify their balance individually.
-        self.bits.iter().all(|bit| bit.verify())
+        for (i, bit) in self.bits.iter().enumerate() {
+            bit.check().map_err(|e| e.at(Location::Bit(i)))?;
+        }
+        Ok(())
+    }
+
+    /// Verify the proof has the number of bits of `params` and is over its generators, rather
+    /// than the ones it holds, reporting the first bit that fails
+    pub fn check_with(&self, params: &Params) -> Result<(), VerificationError> {
+        ensure(
+            self.g == params.g && self.h == params.h,
+            "proof is over the expected generators (g, h)",
+        )?;
+        ensure(
+            self.bits.len() == params.balance_bits,
+            "one binary proof per balance bit",
+        )?;
+        for (i, bit) in self.bits.iter().enumerate() {
+            bit.check_with(&params.g, &params.h)
+                .map_err(|e| e.at(Location::Bit(i)))?;
+        }
+        Ok(())
     }
 
     /// Customer verification process where they confirm the balance was computed correctly
@@ -96,6 +184,103 @@ impl LiabilityProof {
         return self.z() == rhs;
     }
 
+    /// Customer verification that additionally checks the proof is a leaf of the published
+    /// liability tree with the given root
+    pub fn verify_as_customer_with_inclusion(
+        &self,
+        identifier: &[u8],
+        balance: &BigUint,
+        inclusion: &InclusionProof,
+        root: &[u8; 32],
+    ) -> bool {
+        self.verify_as_customer(identifier, balance) && inclusion.verify(self, root)
+    }
+
+    /// Customer verification that additionally checks the balance commitment is summed into
+    /// the root of the published commitment tree
+    pub fn verify_as_customer_with_path(
+        &self,
+        identifier: &[u8],
+        balance: &BigUint,
+        path: &CommitmentPath,
+        root: &CommitmentNode,
+    ) -> bool {
+        self.verify_as_customer(identifier, balance) && path.verify(&self.cid, &self.z(), root)
+    }
+
+    /// Customer Identifier
+    pub fn cid(&self) -> &[u8; 32] {
+        &self.cid
+    }
+
+    /// Encoding of the public part of the proof, leaving out the secrets (n, r). Encodes into
+    /// 32 + (195 * 51) = 9,977 bytes
+    pub fn serialize_public(&self) -> Vec<u8> {
+        let mut out = vec![];
+        out.extend(&self.cid.clone());
+        out.extend(self.bits.iter().flat_map(BinaryProof::serialize));
+        out
+    }
+
+    /// Read the encoding returned by `serialize` with the parameters it was created with. The
+    /// binary proofs are laid out as `params.version` encodes them, the generators held by
+    /// version 1 ones are ignored.
+    pub fn deserialize_with(bytes: &[u8], params: &Params) -> LiabilityProof {
+        let (g, h) = (&params.g, &params.h);
+        let proof_len = BinaryProof::encoded_len(params.version);
+        let end = 32 + proof_len * params.balance_bits;
+        let bits = bytes[32..end]
+            .chunks(proof_len)
+            .map(|proof_bytes| BinaryProof::deserialize_with(proof_bytes, g, h))
+            .collect();
+        LiabilityProof::from_parts(bytes, bits, end, g.clone(), h.clone())
+    }
+
+    /// Read the encoding returned by `serialize_public`, with a binary proof per 195 bytes after
+    /// the cid. The secrets (n, r) are left zero.
+    pub fn deserialize_public_with(bytes: &[u8], params: &Params) -> LiabilityProof {
+        let (g, h) = (&params.g, &params.h);
+        let mut cid = [0; 32];
+        cid.copy_from_slice(&bytes[0..32]);
+        let bits = bytes[32..]
+            .chunks(BinaryProof::ENCODED_LEN)
+            .map(|proof_bytes| BinaryProof::deserialize_with(proof_bytes, g, h))
+            .collect();
+
+        LiabilityProof {
+            g: g.clone(),
+            h: h.clone(),
+            cid,
+            bits,
+            n: BigUint::zero(),
+            r: BigUint::zero(),
+        }
+    }
+
+    /// Assemble a proof from its cid and secrets around the already read binary proofs ending at
+    /// `bits_end`
+    fn from_parts(
+        bytes: &[u8],
+        bits: Vec<BinaryProof>,
+        bits_end: usize,
+        g: Point,
+        h: Point,
+    ) -> LiabilityProof {
+        let mut cid = [0; 32];
+        cid.copy_from_slice(&bytes[0..32]);
+        let n = BigUint::from_bytes_be(&bytes[bits_end..(bits_end + 32)]);
+        let r = BigUint::from_bytes_be(&bytes[(bits_end + 32)..]);
+
+        LiabilityProof {
+            g,
+            h,
+            cid,
+            bits,
+            n,
+            r,
+        }
+    }
+
     /// Commitment to the balance as the sum of the bit commitments
     pub fn z(&self) -> Point {
         let mut z = Point::infinity();
@@ -111,43 +296,71 @@ impl LiabilityProof {
 }
 
 impl Serialize for LiabilityProof {
-    /// Encodes into 32 + (261 * 51) + 39 = 13,382 bytes
+    /// Encodes into 32 + (195 * 51) + 32 + r = 10,041 bytes at most
     fn serialize(&self) -> Vec<u8> {
-        let mut out = vec![];
-        out.extend(&self.cid.clone());
-        out.extend(
-            self.bits
-                .iter()
-                .map(|bit| bit.serialize())
-                .flatten()
-                .collect::<Vec<u8>>(),
-        );
+        let mut out = self.serialize_public();
         out.extend(biguint_to_bytes_be(&self.n, 32));
         out.extend(self.r.to_bytes_be()); // variable length
         out
     }
 }
 
-impl Deserialize for LiabilityProof {
-    fn deserialize(bytes: &[u8]) -> LiabilityProof {
-        let (g, h) = (crate::g(), crate::h());
+#[cfg(feature = "json")]
+impl ToJson for LiabilityProof {
+    fn to_json(&self) -> Value {
+        json!({
+            "cid": hex::encode(&self.cid),
+            "bits": self.bits.iter().map(|bit| bit.to_json()).collect::<Vec<Value>>(),
+        })
+    }
+}
+
+#[cfg(feature = "json")]
+impl LiabilityProof {
+    /// Read the public fields of the JSON representation with the parameters the proof was
+    /// created with. The secrets (n, r) are left zero.
+    pub fn from_json_with(value: &Value, params: &Params) -> Result<LiabilityProof, &'static str> {
+        let (g, h) = (&params.g, &params.h);
+        let cid_bytes = hex_field(value, "cid")?;
+        if cid_bytes.len() != 32 {
+            return Err("customer identifier must be 32 bytes");
+        }
         let mut cid = [0; 32];
-        cid.copy_from_slice(&bytes[0..32]);
-        let bits = bytes[32..(32 + (261 * 51))]
-            .chunks(261)
-            .map(|proof_bytes| BinaryProof::deserialize(proof_bytes))
-            .collect::<Vec<BinaryProof>>();
-        let n = BigUint::from_bytes_be(&bytes[13343..13375]);
-        let r = BigUint::from_bytes_be(&bytes[13375..]);
+        cid.copy_from_slice(&cid_bytes);
 
-        LiabilityProof {
-            g,
-            h,
+        let bits = field(value, "bits")?
+            .as_array()
+            .ok_or("expected an array of bits")?
+            .iter()
+            .map(|bit| BinaryProof::from_json_with(bit, g, h))
+            .collect::<Result<Vec<BinaryProof>, &'static str>>()?;
+        if bits.len() != params.balance_bits {
+            return Err("expected a binary proof per balance bit");
+        }
+
+        Ok(LiabilityProof {
+            g: g.clone(),
+            h: h.clone(),
             cid,
             bits,
-            n,
-            r,
-        }
+            n: BigUint::zero(),
+            r: BigUint::zero(),
+        })
+    }
+}
+
+#[cfg(feature = "json")]
+impl FromJson for LiabilityProof {
+    /// Reads the public fields over the default parameters
+    fn from_json(value: &Value) -> Result<LiabilityProof, &'static str> {
+        LiabilityProof::from_json_with(value, &Params::default())
+    }
+}
+
+impl Deserialize for LiabilityProof {
+    /// Reads the proof over the default parameters
+    fn deserialize(bytes: &[u8]) -> LiabilityProof {
+        LiabilityProof::deserialize_with(bytes, &Params::default())
     }
 }
 
@@ -179,6 +392,32 @@ mod tests {
         assert!(commitment.verify_as_customer(&username[..], &balance) "commitment not able to be verified");
     }
 
+    #[test]
+    fn verify_customer_liability_inclusion() {
+        let (g, h) = (crate::g(), crate::h());
+        let username = b"testuser";
+        let balance = BigUint::from(10u8);
+        let proofs = vec![
+            LiabilityProof::create(&username[..], &balance, g.clone(), h.clone()),
+            LiabilityProof::create(&b"otheruser"[..], &balance, g, h),
+        ];
+        let tree = crate::proofs::LiabilityTree::new(&proofs);
+        let inclusion = tree.prove(proofs[0].cid()).unwrap();
+
+        assert!(proofs[0].verify_as_customer_with_inclusion(
+            &username[..],
+            &balance,
+            &inclusion,
+            &tree.root()
+        ));
+        assert!(!proofs[1].verify_as_customer_with_inclusion(
+            &b"otheruser"[..],
+            &balance,
+            &inclusion,
+            &tree.root()
+        ));
+    }
+
     #[test]
     fn liability_proof_serialization() {
         let g = crate::g();
@@ -189,4 +428,44 @@ mod tests {
         let proof = LiabilityProof::create(&username[..], &balance, g, h);
         let proof2 = LiabilityProof::deserialize(&proof.serialize());
     }
+
+    #[test]
+    fn liability_proof_reads_legacy_encoding() {
+        let (g, h) = (crate::g(), crate::h());
+        let username = b"testuser";
+        let balance = BigUint::from(10u8);
+        let proof = LiabilityProof::create(&username[..], &balance, g, h);
+
+        let mut legacy = proof.cid.to_vec();
+        legacy.extend(proof.bits.iter().flat_map(BinaryProof::serialize_legacy));
+        legacy.extend(biguint_to_bytes_be(&proof.n, 32));
+        legacy.extend(proof.r.to_bytes_be());
+        let compact = proof.serialize();
+        let version_1 = Params {
+            version: 1,
+            ..Params::default()
+        };
+
+        // A quarter smaller without the generators
+        assert_eq!(legacy.len() - compact.len(), 66 * BALANCE_BITS);
+        for (bytes, params) in [(&legacy, &version_1), (&compact, &Params::default())].iter() {
+            let read = LiabilityProof::deserialize_with(bytes, params);
+            assert_eq!(read.serialize(), compact);
+            assert!(read.verify_as_customer(&username[..], &balance));
+        }
+    }
+
+    #[test]
+    fn liability_proof_rejects_substituted_generators() {
+        let params = Params::new(b"test");
+        let balance = BigUint::from(10u8);
+        let proof =
+            LiabilityProof::create(b"testuser", &balance, params.g.clone(), params.h.clone());
+
+        assert!(proof.check().is_ok());
+        assert!(proof.check_with(&params).is_ok());
+        assert!(proof.check_with(&Params::default()).is_err());
+        assert!(proof.check_with(&params.clone().balance_bits(32)).is_err());
+        assert!(!LiabilityProof::deserialize(&proof.serialize()).verify());
+    }
 }
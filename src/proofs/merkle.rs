@@ -0,0 +1,258 @@
+use crate::proofs::LiabilityProof;
+use crate::serialization::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Domain separation so a leaf can never be mistaken for an interior node
+pub(super) const LEAF_PREFIX: u8 = 0;
+pub(super) const NODE_PREFIX: u8 = 1;
+
+/// Merkle tree over the public encodings of a set of liability proofs.
+///
+/// Leaves are sorted by customer identifier so the root doesn't depend on the order the proofs
+/// were generated in. A node without a sibling is carried up to the next level unchanged. The
+/// root of an empty tree is all zeros.
+pub struct LiabilityTree {
+    /// Leaf identifiers in tree order
+    cids: Vec<[u8; 32]>,
+    /// Every level of the tree, starting from the leaves
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl LiabilityTree {
+    pub fn new(proofs: &[LiabilityProof]) -> LiabilityTree {
+        let mut leaves: Vec<([u8; 32], [u8; 32])> = proofs
+            .iter()
+            .map(|proof| (*proof.cid(), leaf_hash(proof)))
+            .collect();
+        leaves.sort();
+
+        let cids = leaves.iter().map(|leaf| leaf.0).collect();
+        let hashes = leaves.into_iter().map(|leaf| leaf.1).collect();
+        let levels = build_levels(hashes, |left, right| Some(node_hash(left, right)))
+            .expect("hashing never fails");
+
+        LiabilityTree { cids, levels }
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        match self.levels[self.levels.len() - 1].first() {
+            Some(root) => *root,
+            None => [0; 32],
+        }
+    }
+
+    /// Proof that the liability proof with customer identifier `cid` is a leaf of the tree
+    pub fn prove(&self, cid: &[u8; 32]) -> Option<InclusionProof> {
+        let index = self.cids.binary_search(cid).ok()?;
+
+        Some(InclusionProof {
+            index,
+            leaves: self.cids.len(),
+            path: siblings(&self.levels, index),
+        })
+    }
+}
+
+/// Path from a leaf of a `LiabilityTree` to its root
+#[derive(Clone, Debug, PartialEq)]
+pub struct InclusionProof {
+    /// Position of the leaf in the tree
+    pub index: usize,
+    /// Number of leaves in the tree
+    pub leaves: usize,
+    /// Sibling hashes from the leaf up, skipping levels where the node has no sibling
+    path: Vec<[u8; 32]>,
+}
+
+impl InclusionProof {
+    /// Verify that `proof` is included in the tree with the given root
+    pub fn verify(&self, proof: &LiabilityProof, root: &[u8; 32]) -> bool {
+        let computed = path_root(
+            leaf_hash(proof),
+            self.index,
+            self.leaves,
+            &self.path,
+            |left, right| Some(node_hash(left, right)),
+        );
+        computed.as_ref() == Some(root)
+    }
+}
+
+impl Serialize for InclusionProof {
+    /// Encodes into 8 + 8 + 32 * path length bytes
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = vec![];
+        out.extend(&(self.index as u64).to_be_bytes());
+        out.extend(&(self.leaves as u64).to_be_bytes());
+        for hash in self.path.iter() {
+            out.extend(hash);
+        }
+        out
+    }
+}
+
+impl Deserialize for InclusionProof {
+    fn deserialize(bytes: &[u8]) -> InclusionProof {
+        let mut index = [0; 8];
+        index.copy_from_slice(&bytes[0..8]);
+        let mut leaves = [0; 8];
+        leaves.copy_from_slice(&bytes[8..16]);
+        let path = bytes[16..]
+            .chunks(32)
+            .map(|chunk| {
+                let mut hash = [0; 32];
+                hash.copy_from_slice(chunk);
+                hash
+            })
+            .collect();
+
+        InclusionProof {
+            index: u64::from_be_bytes(index) as usize,
+            leaves: u64::from_be_bytes(leaves) as usize,
+            path,
+        }
+    }
+}
+
+/// Every level of a tree over `leaves`, starting from the leaves, with `parent` combining two
+/// sibling nodes. A node without a sibling is carried up to the next level unchanged. `None` if
+/// `parent` fails for any pair.
+pub(super) fn build_levels<N, P>(leaves: Vec<N>, parent: P) -> Option<Vec<Vec<N>>>
+where
+    N: Clone,
+    P: Fn(&N, &N) -> Option<N>,
+{
+    let mut levels = vec![leaves];
+    while levels[levels.len() - 1].len() > 1 {
+        let next = levels[levels.len() - 1]
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => parent(left, right),
+                _ => Some(pair[0].clone()),
+            })
+            .collect::<Option<Vec<N>>>()?;
+        levels.push(next);
+    }
+    Some(levels)
+}
+
+/// Sibling nodes on the way from leaf `index` to the root, skipping levels where the node has no
+/// sibling
+pub(super) fn siblings<N: Clone>(levels: &[Vec<N>], index: usize) -> Vec<N> {
+    let mut siblings = vec![];
+    let mut i = index;
+    for level in levels[..(levels.len() - 1)].iter() {
+        if let Some(sibling) = level.get(i ^ 1) {
+            siblings.push(sibling.clone());
+        }
+        i /= 2;
+    }
+    siblings
+}
+
+/// Root of a tree of `leaves` leaves recomputed from `leaf` at `index` and its `siblings`. `None`
+/// if the siblings don't match the shape of the tree or `parent` fails.
+pub(super) fn path_root<N, P>(
+    leaf: N,
+    index: usize,
+    leaves: usize,
+    siblings: &[N],
+    parent: P,
+) -> Option<N>
+where
+    P: Fn(&N, &N) -> Option<N>,
+{
+    if index >= leaves {
+        return None;
+    }
+
+    let mut node = leaf;
+    let mut siblings = siblings.iter();
+    let (mut i, mut width) = (index, leaves);
+    while width > 1 {
+        if i % 2 == 1 {
+            node = parent(siblings.next()?, &node)?;
+        } else if i + 1 < width {
+            node = parent(&node, siblings.next()?)?;
+        }
+        i /= 2;
+        width = (width + 1) / 2;
+    }
+
+    match siblings.next() {
+        None => Some(node),
+        Some(_) => None,
+    }
+}
+
+fn leaf_hash(proof: &LiabilityProof) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.input(&[LEAF_PREFIX]);
+    hasher.input(&proof.serialize_public());
+    to_array(hasher.result().as_slice())
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.input(&[NODE_PREFIX]);
+    hasher.input(left);
+    hasher.input(right);
+    to_array(hasher.result().as_slice())
+}
+
+pub(super) fn to_array(bytes: &[u8]) -> [u8; 32] {
+    let mut out = [0; 32];
+    out.copy_from_slice(bytes);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn inclusion_proofs_verify_for_every_leaf() {
+        let proofs = gen_proofs(5);
+        let tree = LiabilityTree::new(&proofs);
+        let root = tree.root();
+
+        for proof in proofs.iter() {
+            let inclusion = tree.prove(proof.cid()).unwrap();
+            assert!(inclusion.verify(proof, &root));
+
+            let inclusion = InclusionProof::deserialize(&inclusion.serialize());
+            assert!(inclusion.verify(proof, &root));
+        }
+    }
+
+    #[test]
+    fn root_is_independent_of_proof_order() {
+        let mut proofs = gen_proofs(3);
+        let root = LiabilityTree::new(&proofs).root();
+        proofs.reverse();
+
+        assert_eq!(LiabilityTree::new(&proofs).root(), root);
+    }
+
+    #[test]
+    fn inclusion_proof_rejects_other_trees_and_leaves() {
+        let proofs = gen_proofs(4);
+        let tree = LiabilityTree::new(&proofs[..3]);
+        let inclusion = tree.prove(proofs[0].cid()).unwrap();
+
+        assert!(tree.prove(proofs[3].cid()).is_none());
+        assert!(!inclusion.verify(&proofs[3], &tree.root()));
+        assert!(!inclusion.verify(&proofs[0], &LiabilityTree::new(&proofs).root()));
+    }
+
+    fn gen_proofs(num: u8) -> Vec<LiabilityProof> {
+        (0..num)
+            .map(|i| {
+                let id = format!("user{}", i);
+                let (g, h) = (crate::g(), crate::h());
+                LiabilityProof::create(id.as_bytes(), &BigUint::from(i), g, h)
+            })
+            .collect()
+    }
+}
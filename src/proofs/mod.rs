@@ -5,13 +5,32 @@ use sha2::{Digest, Sha256};
 
 mod asset;
 mod binary;
+mod bundle;
+mod commitment_tree;
 mod liability;
+mod merkle;
+mod multisig;
+mod report;
 mod schnorr;
 mod solvency;
+mod sum_tree;
 
+pub use self::asset::offline::{AssetProofRequest, AssetProofResponse, PendingAssetProof};
 pub use self::asset::AssetProof;
+#[cfg(feature = "json")]
+pub(crate) use self::binary::BinaryProof;
+pub use self::bundle::SolvencyBundle;
+pub use self::commitment_tree::{CommitmentNode, CommitmentPath, CommitmentTree};
 pub use self::liability::LiabilityProof;
+pub(crate) use self::liability::BALANCE_BITS;
+pub use self::merkle::{InclusionProof, LiabilityTree};
+pub use self::multisig::MultisigAssetProof;
+pub(crate) use self::report::ensure;
+pub use self::report::{Location, VerificationError, VerificationReport};
+#[cfg(feature = "json")]
+pub(crate) use self::schnorr::SchnorrProof;
 pub use self::solvency::SolvencyProof;
+pub use self::sum_tree::{LiabilityModeReport, SumLeaf, SumNode, SumTree, SumTreeProof};
 
 /// Compute a challenge value from a set of points using the Fiat-Shamir heuristic
 fn compute_challenge(points: &[&Point]) -> Field256 {
@@ -0,0 +1,539 @@
+use crate::fields::Field256;
+use crate::params::Params;
+use crate::proofs::binary::BinaryProof;
+use crate::proofs::report::{ensure, Location, VerificationError};
+use crate::proofs::AssetProof;
+use crate::secp256k1::{point_mul, point_sum, Point};
+use crate::serialization::{Deserialize, Serialize};
+use crate::signer::Signer;
+use num_bigint::BigUint;
+
+/// Asset proof for an output controlled by m of n keys.
+///
+/// Every subset of `threshold` keys is treated as a single aggregated key, y_S = the sum of the
+/// keys in S, which can be spent by whoever knows x_S = the sum of their private keys. An ordinary
+/// asset proof is produced for each y_S with the output balance and the exchange claims at most
+/// one of them:
+///
+/// - Each subset proof commits to p_S = b^s_S * h^v_S with s_S in [0,1].
+/// - `claimed` proves the sum of all p_S is b^s * h^v with s in [0,1], so at most one s_S is 1
+///   and the balance is never counted more than once.
+///
+/// The number of subsets grows as n choose m, which is fine for the 2-of-3 and 3-of-5 setups
+/// common in practice.
+#[derive(Debug, PartialEq)]
+pub struct MultisigAssetProof {
+    g: Point,
+    h: Point,
+
+    /// Public keys able to sign for the output
+    pub keys: Vec<Point>,
+    /// Number of keys required to spend the output
+    pub threshold: usize,
+
+    /// Proofs for the aggregated key of each subset, in lexicographic order of the subsets
+    subsets: Vec<AssetProof>,
+    /// claimed.l == the sum of the subsets' p, committing to the balance if owned
+    claimed: BinaryProof,
+
+    /// for solvency verification, secret value and only known to E
+    pub v: Field256,
+}
+
+/// Largest number of keys of an output with an encodable proof
+const MAX_KEYS: usize = 255;
+
+impl MultisigAssetProof {
+    /// Create a proof for an output held by `keys` with `xs` the private keys known for each of
+    /// them. The output is claimed when at least `threshold` private keys are known.
+    pub fn create(
+        xs: &[Option<Field256>],
+        keys: &[Point],
+        threshold: usize,
+        bal: BigUint,
+        g: &Point,
+        h: &Point,
+    ) -> MultisigAssetProof {
+        if xs.len() != keys.len() {
+            panic!("Expected {} private keys, got {}", keys.len(), xs.len());
+        }
+
+        let owned = |i: usize| xs[i].is_some();
+        let claim = |subset: &[usize], y: &Point, bal: BigUint| {
+            let x = subset.iter().fold(Field256::zero(), |acc, i| {
+                acc + xs[*i].as_ref().expect("key known")
+            });
+            Ok(AssetProof::create(Some(x), y, bal, g, h))
+        };
+
+        MultisigAssetProof::prove(keys, threshold, bal, g, h, owned, claim).expect("proof created")
+    }
+
+    /// Create a proof for an output held by `keys`, claiming it when the signer holds at least
+    /// `threshold` of them. Fails for a threshold of 0 or above the number of keys and for
+    /// outputs with more than 255 keys, which can't be encoded.
+    pub fn create_with_signer(
+        keys: &[Point],
+        threshold: usize,
+        bal: BigUint,
+        signer: &dyn Signer,
+        g: &Point,
+        h: &Point,
+    ) -> Result<MultisigAssetProof, &'static str> {
+        let owned = |i: usize| signer.has_key(&keys[i]);
+        let claim = |subset: &[usize], y: &Point, bal: BigUint| {
+            let subset_keys: Vec<Point> = subset.iter().map(|i| keys[*i].clone()).collect();
+            AssetProof::create_signed(&subset_keys, y, bal, signer, g, h)
+        };
+
+        MultisigAssetProof::prove(keys, threshold, bal, g, h, owned, claim)
+    }
+
+    /// Prove every subset, claiming the first one where `owned` holds for all of its keys
+    fn prove<O, C>(
+        keys: &[Point],
+        threshold: usize,
+        bal: BigUint,
+        g: &Point,
+        h: &Point,
+        owned: O,
+        claim: C,
+    ) -> Result<MultisigAssetProof, &'static str>
+    where
+        O: Fn(usize) -> bool,
+        C: Fn(&[usize], &Point, BigUint) -> Result<AssetProof, &'static str>,
+    {
+        if threshold == 0 || threshold > keys.len() {
+            return Err("invalid multisig threshold");
+        }
+        // The encoding holds n and m in a byte each
+        if keys.len() > MAX_KEYS {
+            return Err("multisig output has more than 255 keys");
+        }
+
+        let claimed_subset = subsets(keys.len(), threshold)
+            .into_iter()
+            .find(|subset| subset.iter().all(|i| owned(*i)));
+
+        let subsets = subsets(keys.len(), threshold)
+            .into_iter()
+            .map(|subset| {
+                let y = aggregate_key(keys, &subset);
+                if Some(&subset) == claimed_subset.as_ref() {
+                    claim(&subset, &y, bal.clone())
+                } else {
+                    Ok(AssetProof::create(None, &y, bal.clone(), g, h))
+                }
+            })
+            .collect::<Result<Vec<AssetProof>, &'static str>>()?;
+
+        let s = if claimed_subset.is_some() {
+            Field256::one()
+        } else {
+            Field256::zero()
+        };
+        let v = subsets
+            .iter()
+            .fold(Field256::zero(), |acc, proof| acc + &proof.v);
+        let b = point_mul(g.clone(), &Field256::new(bal));
+        let claimed = BinaryProof::create(&s, &v, &b, h);
+
+        Ok(MultisigAssetProof {
+            g: g.clone(),
+            h: h.clone(),
+            keys: keys.to_vec(),
+            threshold,
+            subsets,
+            claimed,
+            v,
+        })
+    }
+
+    /// Verify if the proof is valid or not
+    pub fn verify(&self) -> bool {
+        self.check().is_ok()
+    }
+
+    /// Verify the proof, reporting the first failed check
+    pub fn check(&self) -> Result<(), VerificationError> {
+        self.check_subsets(AssetProof::check)
+    }
+
+    /// Verify the proof is over the generators of `params`, rather than the ones it holds,
+    /// reporting the first failed check
+    pub fn check_with(&self, params: &Params) -> Result<(), VerificationError> {
+        ensure(
+            self.g == params.g && self.h == params.h,
+            "proof is over the expected generators (g, h)",
+        )?;
+        self.check_subsets(|proof| proof.check_with(params))
+    }
+
+    /// Verify the proof, checking each subset proof with `check_subset`
+    fn check_subsets<F>(&self, check_subset: F) -> Result<(), VerificationError>
+    where
+        F: Fn(&AssetProof) -> Result<(), VerificationError>,
+    {
+        let subsets = subsets(self.keys.len(), self.threshold);
+        ensure(
+            !subsets.is_empty() && subsets.len() == self.subsets.len(),
+            "one subset proof per m-subset of the keys",
+        )?;
+
+        // Every subset proof is over its aggregated key and the same balance commitment
+        let b = self.subsets[0].b_ref();
+        for (i, (subset, proof)) in subsets.iter().zip(self.subsets.iter()).enumerate() {
+            let checked = ensure(
+                proof.y == aggregate_key(&self.keys, subset),
+                "subset proof is over the aggregated subset key",
+            )
+            .and_then(|_| ensure(proof.b_ref() == b, "subset proofs share the balance b"))
+            .and_then(|_| check_subset(proof));
+            checked.map_err(|e| e.at(Location::Subset(i)))?;
+        }
+
+        // At most one subset is claimed
+        let p_sum = point_sum(
+            &self
+                .subsets
+                .iter()
+                .map(|proof| proof.p_ref())
+                .collect::<Vec<_>>(),
+        );
+        let (claimed_g, claimed_h) = self.claimed.generators();
+        ensure(
+            claimed_g == b && claimed_h == &self.h,
+            "claimed proof is over the generators (b, h)",
+        )?;
+        ensure(
+            self.claimed.l == p_sum,
+            "claimed commitment is the sum of the subset commitments",
+        )?;
+        self.claimed.check().map_err(|e| e.at(Location::Claimed))
+    }
+
+    /// Commitment to the balance of the output shared by every subset proof, b = g^bal
+    pub fn b_ref(&self) -> &Point {
+        self.subsets[0].b_ref()
+    }
+
+    /// Commitment to the balance of the output if owned, p = b^s * h^v
+    pub fn p_ref(&self) -> &Point {
+        &self.claimed.l
+    }
+
+    /// Read the compact encoding returned by `serialize` with the parameters it was created with
+    pub fn deserialize_with(bytes: &[u8], params: &Params) -> MultisigAssetProof {
+        MultisigAssetProof::read(bytes, params).expect("valid multisig proof")
+    }
+
+    /// Read the encoding returned by `serialize_public`. The secrets v are left zero.
+    pub fn deserialize_public_with(bytes: &[u8], params: &Params) -> MultisigAssetProof {
+        MultisigAssetProof::read_public(bytes, params).expect("valid multisig proof")
+    }
+
+    /// Read an untrusted encoding returned by `serialize`. Fails if the threshold is invalid or
+    /// the length doesn't match the number of subsets, before any subset is enumerated.
+    pub fn read(bytes: &[u8], params: &Params) -> Result<MultisigAssetProof, &'static str> {
+        decode(bytes, params, Layout::Compact)
+    }
+
+    /// Read an untrusted encoding returned by `serialize_public`, like `read`
+    pub fn read_public(bytes: &[u8], params: &Params) -> Result<MultisigAssetProof, &'static str> {
+        decode(bytes, params, Layout::Public)
+    }
+
+    /// Length of the public encoding of a proof for an m of n output, or None if it overflows
+    pub fn public_encoded_len(n: usize, m: usize) -> Option<usize> {
+        let subsets_len = count_subsets(n, m)?.checked_mul(AssetProof::PUBLIC_ENCODED_LEN)?;
+        subsets_len.checked_add(2 + 33 * n + BinaryProof::ENCODED_LEN)
+    }
+
+    /// Encoding of the public part of the proof, leaving out the secret v of the proof and of
+    /// every subset proof. Encodes into 2 + 33 * n + 521 * (n choose m) + 195 bytes
+    pub fn serialize_public(&self) -> Vec<u8> {
+        let mut out = self.encode_keys();
+        for proof in self.subsets.iter() {
+            out.extend(proof.serialize_public());
+        }
+        out.extend(self.claimed.serialize());
+        out
+    }
+
+    fn encode_keys(&self) -> Vec<u8> {
+        let mut out = vec![self.keys.len() as u8, self.threshold as u8];
+        for key in self.keys.iter() {
+            out.extend(key.serialize());
+        }
+        out
+    }
+}
+
+/// Layouts of the encoded proof
+#[derive(Clone, Copy, PartialEq)]
+enum Layout {
+    Compact,
+    Public,
+}
+
+/// Every subset of size m of the indices 0..n, in lexicographic order
+fn subsets(n: usize, m: usize) -> Vec<Vec<usize>> {
+    if m == 0 || m > n {
+        return vec![];
+    }
+
+    let mut out = vec![];
+    let mut subset: Vec<usize> = (0..m).collect();
+    loop {
+        out.push(subset.clone());
+
+        // Advance the right most index that isn't at its maximum and reset those after it
+        let i = match (0..m).rev().find(|i| subset[*i] < n - m + i) {
+            None => return out,
+            Some(i) => i,
+        };
+        subset[i] += 1;
+        for j in (i + 1)..m {
+            subset[j] = subset[j - 1] + 1;
+        }
+    }
+}
+
+/// Number of subsets of size m of the indices 0..n, or None if it overflows
+fn count_subsets(n: usize, m: usize) -> Option<usize> {
+    if m == 0 || m > n {
+        return Some(0);
+    }
+    // After step i the count is n choose (i + 1), so every division is exact
+    (0..m).try_fold(1usize, |count, i| Some(count.checked_mul(n - i)? / (i + 1)))
+}
+
+fn aggregate_key(keys: &[Point], subset: &[usize]) -> Point {
+    point_sum(&subset.iter().map(|i| &keys[*i]).collect::<Vec<_>>())
+}
+
+impl Serialize for MultisigAssetProof {
+    /// Encodes into 2 + 33 * n + 553 * (n choose m) + 195 + 32 bytes
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = self.encode_keys();
+        for proof in self.subsets.iter() {
+            out.extend(proof.serialize());
+        }
+        out.extend(self.claimed.serialize());
+        out.extend(self.v.serialize());
+        out
+    }
+}
+
+impl Deserialize for MultisigAssetProof {
+    /// Reads the proof over the default generators
+    fn deserialize(bytes: &[u8]) -> MultisigAssetProof {
+        MultisigAssetProof::deserialize_with(bytes, &Params::default())
+    }
+}
+
+fn decode(
+    bytes: &[u8],
+    params: &Params,
+    layout: Layout,
+) -> Result<MultisigAssetProof, &'static str> {
+    let (g, h) = (&params.g, &params.h);
+    if bytes.len() < 2 {
+        return Err("multisig proof too short");
+    }
+    let (n, threshold) = (bytes[0] as usize, bytes[1] as usize);
+    let (proof_len, secret_len) = match layout {
+        Layout::Compact => (AssetProof::ENCODED_LEN, 32),
+        Layout::Public => (AssetProof::PUBLIC_ENCODED_LEN, 0),
+    };
+    let count = count_subsets(n, threshold)
+        .filter(|count| *count > 0)
+        .ok_or("invalid multisig threshold")?;
+    let len = count.checked_mul(proof_len).and_then(|subsets_len| {
+        subsets_len.checked_add(2 + 33 * n + BinaryProof::ENCODED_LEN + secret_len)
+    });
+    if len != Some(bytes.len()) {
+        return Err("invalid multisig proof length");
+    }
+
+    let keys_end = 2 + 33 * n;
+    let keys = bytes[2..keys_end]
+        .chunks(33)
+        .map(Point::deserialize)
+        .collect();
+
+    let subsets_end = keys_end + proof_len * count;
+    let subsets: Vec<AssetProof> = bytes[keys_end..subsets_end]
+        .chunks(proof_len)
+        .map(|proof_bytes| match layout {
+            Layout::Compact => AssetProof::deserialize_with(proof_bytes, params),
+            Layout::Public => AssetProof::deserialize_public_with(proof_bytes, params),
+        })
+        .collect();
+    let claimed_end = subsets_end + BinaryProof::ENCODED_LEN;
+    // The claimed proof is over the generators (b, h) of the subset proofs
+    let b = subsets.first().map_or(g, AssetProof::b_ref);
+    let claimed = BinaryProof::deserialize_with(&bytes[subsets_end..claimed_end], b, h);
+    let v = match layout {
+        Layout::Compact => Field256::deserialize(&bytes[claimed_end..(claimed_end + 32)]),
+        Layout::Public => Field256::zero(),
+    };
+
+    Ok(MultisigAssetProof {
+        g: g.clone(),
+        h: h.clone(),
+        keys,
+        threshold,
+        subsets,
+        claimed,
+        v,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::MemorySigner;
+
+    #[test]
+    fn subsets_are_lexicographic() {
+        assert_eq!(subsets(3, 2), vec![vec![0, 1], vec![0, 2], vec![1, 2]]);
+        assert_eq!(subsets(4, 3).len(), 4);
+        assert_eq!(subsets(5, 3).len(), 10);
+        assert_eq!(subsets(2, 3).len(), 0);
+        assert_eq!(count_subsets(5, 3), Some(10));
+        assert_eq!(count_subsets(255, 127), None);
+    }
+
+    #[test]
+    fn verify_multisig_proof_with_threshold_keys() {
+        let (g, h) = (crate::g(), crate::h());
+        let (xs, keys) = gen_keys(3);
+        let known = vec![None, Some(xs[1].clone()), Some(xs[2].clone())];
+
+        let proof = MultisigAssetProof::create(&known, &keys, 2, BigUint::from(10u8), &g, &h);
+
+        assert!(proof.verify(), "proof not able to be verified");
+    }
+
+    #[test]
+    fn verify_multisig_proof_below_threshold() {
+        let (g, h) = (crate::g(), crate::h());
+        let (xs, keys) = gen_keys(3);
+        let known = vec![Some(xs[0].clone()), None, None];
+
+        let proof = MultisigAssetProof::create(&known, &keys, 2, BigUint::from(10u8), &g, &h);
+
+        assert!(proof.verify(), "proof not able to be verified");
+    }
+
+    #[test]
+    fn verify_multisig_proof_with_signer() {
+        let (g, h) = (crate::g(), crate::h());
+        let (xs, keys) = gen_keys(3);
+        let signer = MemorySigner::new(vec![xs[0].clone(), xs[2].clone()]);
+
+        let proof =
+            MultisigAssetProof::create_with_signer(&keys, 2, BigUint::from(10u8), &signer, &g, &h)
+                .unwrap();
+
+        assert!(proof.verify(), "proof not able to be verified");
+    }
+
+    #[test]
+    fn multisig_proof_rejects_unencodable_key_count() {
+        let (g, h) = (crate::g(), crate::h());
+        let (_, keys) = gen_keys(256);
+        let signer = MemorySigner::new(vec![]);
+
+        let proof = MultisigAssetProof::create_with_signer(
+            &keys,
+            256,
+            BigUint::from(10u8),
+            &signer,
+            &g,
+            &h,
+        );
+
+        assert!(proof.is_err());
+    }
+
+    #[test]
+    fn multisig_proof_rejects_invalid_threshold() {
+        let (g, h) = (crate::g(), crate::h());
+        let (_, keys) = gen_keys(3);
+        let signer = MemorySigner::new(vec![]);
+
+        for threshold in [0, 4].iter() {
+            let proof = MultisigAssetProof::create_with_signer(
+                &keys,
+                *threshold,
+                BigUint::from(10u8),
+                &signer,
+                &g,
+                &h,
+            );
+            assert!(proof.is_err());
+        }
+    }
+
+    #[test]
+    fn multisig_read_rejects_invalid_encodings() {
+        let (g, h) = (crate::g(), crate::h());
+        let (xs, keys) = gen_keys(3);
+        let known: Vec<Option<Field256>> = xs.into_iter().map(Some).collect();
+        let proof = MultisigAssetProof::create(&known, &keys, 2, BigUint::from(10u8), &g, &h);
+        let params = Params::default();
+        let mut bytes = proof.serialize();
+
+        assert!(MultisigAssetProof::read(&bytes, &params).is_ok());
+        assert!(MultisigAssetProof::read(&bytes[..bytes.len() - 1], &params).is_err());
+        assert!(MultisigAssetProof::read_public(&bytes, &params).is_err());
+
+        // A header claiming 200 of 255 keys is rejected without enumerating its subsets
+        bytes[0] = 255;
+        bytes[1] = 200;
+        assert!(MultisigAssetProof::read(&bytes, &params).is_err());
+        bytes[1] = 0;
+        assert!(MultisigAssetProof::read(&bytes, &params).is_err());
+    }
+
+    #[test]
+    fn multisig_proof_rejects_substituted_keys() {
+        let (g, h) = (crate::g(), crate::h());
+        let (xs, keys) = gen_keys(3);
+        let known: Vec<Option<Field256>> = xs.into_iter().map(Some).collect();
+        let mut proof = MultisigAssetProof::create(&known, &keys, 2, BigUint::from(10u8), &g, &h);
+
+        proof.keys[0] = point_mul(g, &Field256::from(99));
+
+        assert!(!proof.verify(), "proof verified");
+    }
+
+    #[test]
+    fn multisig_proof_serialization() {
+        let (g, h) = (crate::g(), crate::h());
+        let (xs, keys) = gen_keys(3);
+        let known: Vec<Option<Field256>> = xs.into_iter().map(Some).collect();
+        let proof = MultisigAssetProof::create(&known, &keys, 2, BigUint::from(10u8), &g, &h);
+        let proof2 = MultisigAssetProof::deserialize(&proof.serialize());
+
+        assert_eq!(proof, proof2);
+
+        let public = proof.serialize_public();
+        assert_eq!(public.len(), 2 + 33 * 3 + 521 * 3 + 195);
+        assert_eq!(
+            MultisigAssetProof::public_encoded_len(3, 2),
+            Some(public.len())
+        );
+        let proof3 = MultisigAssetProof::deserialize_public_with(&public, &Params::default());
+        assert!(proof3.verify());
+        assert_eq!(proof3.serialize_public(), public);
+    }
+
+    fn gen_keys(n: i32) -> (Vec<Field256>, Vec<Point>) {
+        let xs: Vec<Field256> = (1..=n).map(Field256::from).collect();
+        let keys = xs.iter().map(|x| point_mul(Point::g(), x)).collect();
+        (xs, keys)
+    }
+}
@@ -0,0 +1,149 @@
+use std::fmt;
+
+/// A part of a proof or bundle a failed check is nested in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Location {
+    /// Asset proof at this index of the published asset proofs
+    Asset(usize),
+    /// Multisig asset proof at this index of the published multisig proofs
+    MultisigAsset(usize),
+    /// Liability proof at this index of the published liability proofs
+    Liability(usize),
+    /// Binary proof of this bit of a liability proof's balance
+    Bit(usize),
+    /// Binary proof over the balance commitment of an asset proof
+    BalanceCommitment,
+    /// Asset proof of the m-subset at this index of a multisig asset proof
+    Subset(usize),
+    /// Binary proof that at most one subset of a multisig asset proof is claimed
+    Claimed,
+    /// Proof of solvency
+    Solvency,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Location::Asset(i) => write!(f, "asset proof {}", i),
+            Location::MultisigAsset(i) => write!(f, "multisig asset proof {}", i),
+            Location::Liability(i) => write!(f, "liability proof {}", i),
+            Location::Bit(i) => write!(f, "bit {}", i),
+            Location::BalanceCommitment => write!(f, "balance commitment"),
+            Location::Subset(i) => write!(f, "subset {}", i),
+            Location::Claimed => write!(f, "claimed subset"),
+            Location::Solvency => write!(f, "solvency proof"),
+        }
+    }
+}
+
+/// A failed verification check and where it happened, outermost location first. For example a
+/// bad bit of a liability proof reports `liability proof 4032 > bit 17: binary proof equation
+/// h^r0 = a0 * l^(c - c1)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerificationError {
+    pub location: Vec<Location>,
+    /// The check that failed
+    pub check: &'static str,
+}
+
+impl VerificationError {
+    pub fn new(check: &'static str) -> VerificationError {
+        VerificationError {
+            location: vec![],
+            check,
+        }
+    }
+
+    /// Nest the error inside `location`
+    pub fn at(mut self, location: Location) -> VerificationError {
+        self.location.insert(0, location);
+        self
+    }
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, location) in self.location.iter().enumerate() {
+            if i > 0 {
+                write!(f, " > ")?;
+            }
+            write!(f, "{}", location)?;
+        }
+        if !self.location.is_empty() {
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.check)
+    }
+}
+
+/// Every failed check found while verifying a bundle and the proofs it was computed from
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VerificationReport {
+    pub failures: Vec<VerificationError>,
+    /// Number of further failed checks left out of `failures` to bound its size
+    pub omitted: usize,
+}
+
+impl VerificationReport {
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty() && self.omitted == 0
+    }
+
+    /// Record the failure of `result`, if any
+    pub fn record(&mut self, result: Result<(), VerificationError>) {
+        if let Err(e) = result {
+            self.failures.push(e);
+        }
+    }
+
+    /// Record the failure of `result`, only counting it once `limit` failures are held
+    pub(crate) fn record_up_to(&mut self, result: Result<(), VerificationError>, limit: usize) {
+        match result {
+            Err(_) if self.failures.len() >= limit => self.omitted += 1,
+            result => self.record(result),
+        }
+    }
+}
+
+impl fmt::Display for VerificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_ok() {
+            return writeln!(f, "all checks passed");
+        }
+
+        writeln!(f, "{} checks failed", self.failures.len() + self.omitted)?;
+        for failure in self.failures.iter() {
+            writeln!(f, "  {}", failure)?;
+        }
+        if self.omitted > 0 {
+            writeln!(f, "  and {} more", self.omitted)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fail with `check` unless `holds`
+pub(crate) fn ensure(holds: bool, check: &'static str) -> Result<(), VerificationError> {
+    if holds {
+        Ok(())
+    } else {
+        Err(VerificationError::new(check))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verification_error_display() {
+        let error = VerificationError::new("binary proof equation h^r0 = a0 * l^(c - c1)")
+            .at(Location::Bit(17))
+            .at(Location::Liability(4032));
+
+        assert_eq!(
+            error.to_string(),
+            "liability proof 4032 > bit 17: binary proof equation h^r0 = a0 * l^(c - c1)"
+        );
+    }
+}
@@ -1,7 +1,11 @@
 use crate::fields::Field256;
+#[cfg(feature = "json")]
+use crate::json::{from_field, FromJson, ToJson};
 use crate::proofs::compute_challenge;
 use crate::secp256k1::{point_add, point_mul, Point};
 use crate::serialization::{Deserialize, Serialize};
+#[cfg(feature = "json")]
+use serde_json::{json, Value};
 
 #[derive(Debug, PartialEq)]
 pub struct SchnorrProof {
@@ -25,6 +29,11 @@ impl SchnorrProof {
         SchnorrProof { s, g, y, t }
     }
 
+    /// The point y = g^x the proof is about
+    pub fn y(&self) -> &Point {
+        &self.y
+    }
+
     /// Verify if the commitment is valid or not
     pub fn verify(&self) -> bool {
         // g^s
@@ -42,6 +51,18 @@ impl SchnorrProof {
         // g^s == t * y^c
         lhs == rhs
     }
+
+    /// Read the encoding returned by `serialize` over the generator g, ignoring the one it holds
+    pub fn deserialize_with(bytes: &[u8], g: &Point) -> SchnorrProof {
+        let mut proof = SchnorrProof::deserialize(bytes);
+        proof.g = g.clone();
+        proof
+    }
+
+    /// The generator g the proof is over
+    pub(crate) fn g(&self) -> &Point {
+        &self.g
+    }
 }
 
 impl Serialize for SchnorrProof {
@@ -56,6 +77,30 @@ impl Serialize for SchnorrProof {
     }
 }
 
+#[cfg(feature = "json")]
+impl ToJson for SchnorrProof {
+    fn to_json(&self) -> Value {
+        json!({
+            "s": self.s.to_json(),
+            "g": self.g.to_json(),
+            "y": self.y.to_json(),
+            "t": self.t.to_json(),
+        })
+    }
+}
+
+#[cfg(feature = "json")]
+impl FromJson for SchnorrProof {
+    fn from_json(value: &Value) -> Result<SchnorrProof, &'static str> {
+        Ok(SchnorrProof {
+            s: from_field(value, "s")?,
+            g: from_field(value, "g")?,
+            y: from_field(value, "y")?,
+            t: from_field(value, "t")?,
+        })
+    }
+}
+
 impl Deserialize for SchnorrProof {
     fn deserialize(bytes: &[u8]) -> SchnorrProof {
         let s = Field256::deserialize(&bytes[0..32]);
@@ -92,4 +137,16 @@ mod test {
 
         assert_eq!(proof, proof2)
     }
+
+    #[test]
+    fn schnorr_rejects_substituted_generator() {
+        let g = Point::g();
+        let y = point_mul(Point::g(), &Field256::from(123));
+
+        // Proving knowledge of 1 with y as the generator
+        let forged = SchnorrProof::create(Field256::one(), y.clone(), y);
+
+        assert!(forged.verify());
+        assert!(!SchnorrProof::deserialize_with(&forged.serialize(), &g).verify());
+    }
 }
@@ -1,8 +1,15 @@
 use crate::fields::Field256;
+#[cfg(feature = "json")]
+use crate::json::{FromJson, ToJson};
+use crate::params::Params;
+use crate::proofs::report::{ensure, VerificationError};
 use crate::proofs::schnorr::SchnorrProof;
-use crate::proofs::{AssetProof, LiabilityProof};
+use crate::proofs::{AssetProof, LiabilityProof, MultisigAssetProof};
 use crate::secp256k1::{point_add, point_inverse, point_sum, Point};
+use crate::serialization::{Deserialize, Serialize};
 use num_bigint::{BigUint, ToBigInt};
+#[cfg(feature = "json")]
+use serde_json::Value;
 
 pub struct SolvencyProof {
     schnorr: SchnorrProof,
@@ -14,9 +21,17 @@ impl SolvencyProof {
         liability_proofs: &[LiabilityProof],
         h: Point,
     ) -> SolvencyProof {
-        let asset_commitments: Vec<&Point> =
-            asset_proofs.iter().map(|proof| proof.p_ref()).collect();
-        let z_assets = point_sum(&asset_commitments);
+        SolvencyProof::create_with_multisig(asset_proofs, &[], liability_proofs, h)
+    }
+
+    /// Create a proof of solvency where some of the assets are held in multisig outputs
+    pub fn create_with_multisig(
+        asset_proofs: &[AssetProof],
+        multisig_proofs: &[MultisigAssetProof],
+        liability_proofs: &[LiabilityProof],
+        h: Point,
+    ) -> SolvencyProof {
+        let z_assets = asset_commitment(asset_proofs, multisig_proofs);
         // TODO: This double map to get a reference to the point is pretty gross
         let liability_commitments: Vec<Point> =
             liability_proofs.iter().map(|proof| proof.z()).collect();
@@ -26,7 +41,11 @@ impl SolvencyProof {
 
         let z_solvency = point_add(z_assets, &point_inverse(z_liabilities));
 
-        let v_sum: BigUint = asset_proofs.iter().map(|proof| &proof.v.value).sum();
+        let v_sum: BigUint = asset_proofs
+            .iter()
+            .map(|proof| &proof.v.value)
+            .chain(multisig_proofs.iter().map(|proof| &proof.v.value))
+            .sum();
         let r_sum: BigUint = liability_proofs.iter().map(|proof| &proof.r).sum();
         let k = Field256::from(v_sum.to_bigint().unwrap() - r_sum.to_bigint().unwrap());
 
@@ -35,7 +54,108 @@ impl SolvencyProof {
     }
 
     pub fn verify(&self) -> bool {
-        self.schnorr.verify()
+        self.check().is_ok()
+    }
+
+    /// Verify the proof, reporting the failed check
+    pub fn check(&self) -> Result<(), VerificationError> {
+        ensure(
+            self.schnorr.verify(),
+            "schnorr proof equation g^s = t * y^c",
+        )
+    }
+
+    /// Verify the proof is over the generator h of `params`, rather than the one it holds,
+    /// reporting the failed check
+    pub fn check_with(&self, params: &Params) -> Result<(), VerificationError> {
+        ensure(
+            self.schnorr.g() == &params.h,
+            "schnorr proof is over the generator h",
+        )?;
+        self.check()
+    }
+
+    /// Read the encoding returned by `serialize` over the generator h of `params`
+    pub fn deserialize_with(bytes: &[u8], params: &Params) -> SolvencyProof {
+        SolvencyProof {
+            schnorr: SchnorrProof::deserialize_with(bytes, &params.h),
+        }
+    }
+
+    /// Check the proof was computed from the given asset proofs and total liability commitment,
+    /// such as the root of a `CommitmentTree`
+    pub fn verify_commitments(
+        &self,
+        asset_proofs: &[AssetProof],
+        multisig_proofs: &[MultisigAssetProof],
+        liability_commitment: &Point,
+    ) -> bool {
+        self.verify_totals(
+            &asset_commitment(asset_proofs, multisig_proofs),
+            liability_commitment,
+        )
+    }
+
+    /// Check the proof was computed from the sum of the asset balance commitments and the total
+    /// liability commitment, for verifiers that accumulate the sums themselves
+    pub fn verify_totals(&self, asset_commitment: &Point, liability_commitment: &Point) -> bool {
+        let z_solvency = point_add(
+            asset_commitment.clone(),
+            &point_inverse(liability_commitment.clone()),
+        );
+        *self.schnorr.y() == z_solvency
+    }
+}
+
+/// Sum of the commitments to the balance of every asset
+fn asset_commitment(asset_proofs: &[AssetProof], multisig_proofs: &[MultisigAssetProof]) -> Point {
+    let asset_commitments: Vec<&Point> = asset_proofs
+        .iter()
+        .map(|proof| proof.p_ref())
+        .chain(multisig_proofs.iter().map(|proof| proof.p_ref()))
+        .collect();
+    point_sum(&asset_commitments)
+}
+
+impl Serialize for SolvencyProof {
+    /// Encodes into the 131 bytes of the underlying schnorr proof
+    fn serialize(&self) -> Vec<u8> {
+        self.schnorr.serialize()
+    }
+}
+
+#[cfg(feature = "json")]
+impl ToJson for SolvencyProof {
+    /// The fields of the underlying schnorr proof
+    fn to_json(&self) -> Value {
+        self.schnorr.to_json()
+    }
+}
+
+#[cfg(feature = "json")]
+impl SolvencyProof {
+    /// Read the JSON representation, rejecting proofs over any generator but the h of `params`
+    pub fn from_json_with(value: &Value, params: &Params) -> Result<SolvencyProof, &'static str> {
+        let schnorr = SchnorrProof::from_json(value)?;
+        if schnorr.g() != &params.h {
+            return Err("solvency proof is not over the generator h");
+        }
+        Ok(SolvencyProof { schnorr })
+    }
+}
+
+#[cfg(feature = "json")]
+impl FromJson for SolvencyProof {
+    /// Rejects proofs over any generator but the default h
+    fn from_json(value: &Value) -> Result<SolvencyProof, &'static str> {
+        SolvencyProof::from_json_with(value, &Params::default())
+    }
+}
+
+impl Deserialize for SolvencyProof {
+    /// Reads the proof over the default generator h
+    fn deserialize(bytes: &[u8]) -> SolvencyProof {
+        SolvencyProof::deserialize_with(bytes, &Params::default())
     }
 }
 
@@ -63,4 +183,46 @@ mod tests {
 
         assert!(commitment.verify() "commitment not able to be verified");
     }
+
+    #[test]
+    fn solvency_with_multisig_assets() {
+        let g = crate::g();
+        let h = crate::h();
+
+        let x = Field256::from(1);
+        let y = &point_mul(Point::g(), &x);
+        let asset = AssetProof::create(Some(x), y, BigUint::from(4u8), &g, &h);
+
+        let xs: Vec<Field256> = (2..5).map(Field256::from).collect();
+        let keys: Vec<Point> = xs.iter().map(|x| point_mul(Point::g(), x)).collect();
+        let known = vec![Some(xs[0].clone()), None, Some(xs[2].clone())];
+        let multisig = MultisigAssetProof::create(&known, &keys, 2, BigUint::from(6u8), &g, &h);
+
+        let username = b"testuser";
+        let balance = BigUint::from(10u8);
+        let liability = LiabilityProof::create(&username[..], &balance, g, h);
+
+        let h = crate::h();
+        let commitment =
+            SolvencyProof::create_with_multisig(&[asset], &[multisig], &[liability], h);
+
+        assert!(commitment.verify(), "commitment not able to be verified");
+    }
+
+    #[test]
+    fn solvency_rejects_substituted_generator() {
+        let (g, h) = (crate::g(), crate::h());
+        let liability = LiabilityProof::create(b"testuser", &BigUint::from(10u8), g, h);
+
+        // Claim solvency without any assets by proving z = z^1 over the generator z
+        let z = point_inverse(liability.z());
+        let forged = SolvencyProof {
+            schnorr: SchnorrProof::create(Field256::one(), z.clone(), z),
+        };
+
+        assert!(forged.check().is_ok());
+        assert!(forged.verify_totals(&Point::infinity(), &liability.z()));
+        assert!(forged.check_with(&Params::default()).is_err());
+        assert!(!SolvencyProof::deserialize(&forged.serialize()).verify());
+    }
 }
@@ -0,0 +1,321 @@
+use crate::bigint::biguint_to_bytes_be;
+use crate::params::Params;
+use crate::proofs::liability::{compute_cid, LiabilityProof, BALANCE_BITS};
+use crate::proofs::merkle::{
+    build_levels, path_root, siblings, to_array, LEAF_PREFIX, NODE_PREFIX,
+};
+use crate::serialization::{Deserialize, Serialize};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// Node of a `SumTree`: the total balance below it and a hash over its children including their
+/// balances, so a node can't claim a different split of the same total.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SumNode {
+    pub sum: u64,
+    pub hash: [u8; 32],
+}
+
+impl SumNode {
+    fn leaf(cid: &[u8; 32], balance: u64) -> SumNode {
+        let mut hasher = Sha256::new();
+        hasher.input(&[LEAF_PREFIX]);
+        hasher.input(&cid[..]);
+        hasher.input(&balance.to_be_bytes());
+        SumNode {
+            sum: balance,
+            hash: to_array(hasher.result().as_slice()),
+        }
+    }
+
+    fn parent(left: &SumNode, right: &SumNode) -> Option<SumNode> {
+        let sum = left.sum.checked_add(right.sum)?;
+        let mut hasher = Sha256::new();
+        hasher.input(&[NODE_PREFIX]);
+        hasher.input(&left.sum.to_be_bytes());
+        hasher.input(&left.hash);
+        hasher.input(&right.sum.to_be_bytes());
+        hasher.input(&right.hash);
+        Some(SumNode {
+            sum,
+            hash: to_array(hasher.result().as_slice()),
+        })
+    }
+}
+
+impl Serialize for SumNode {
+    /// Encodes into 8 + 32 = 40 bytes
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = self.sum.to_be_bytes().to_vec();
+        out.extend(&self.hash);
+        out
+    }
+}
+
+impl Deserialize for SumNode {
+    fn deserialize(bytes: &[u8]) -> SumNode {
+        let mut sum = [0; 8];
+        sum.copy_from_slice(&bytes[0..8]);
+        SumNode {
+            sum: u64::from_be_bytes(sum),
+            hash: to_array(&bytes[8..40]),
+        }
+    }
+}
+
+/// A customer's leaf in a `SumTree`. The salt is secret and only given to the customer it
+/// belongs to, like the salt of a `LiabilityProof`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SumLeaf {
+    /// Customer Identifier
+    pub cid: [u8; 32],
+    /// Customer Identifier Salt
+    pub n: BigUint,
+    pub balance: u64,
+}
+
+impl SumLeaf {
+    pub fn new(identifier: &[u8], balance: &BigUint, n: BigUint) -> Result<SumLeaf, &'static str> {
+        if balance.bits() > BALANCE_BITS {
+            return Err("balance too large");
+        }
+
+        Ok(SumLeaf {
+            cid: compute_cid(identifier, &n),
+            n,
+            balance: balance.to_u64().ok_or("balance too large")?,
+        })
+    }
+}
+
+/// Maxwell style Merkle sum tree proof of liabilities.
+///
+/// A simpler alternative to publishing a `LiabilityProof` per customer: every node holds the sum
+/// of the balances below it, so the root reveals the total liabilities and a customer checks
+/// their balance was counted with a logarithmic path of sibling nodes. Unlike the commitment
+/// based mode the total and the sibling balances along each path are disclosed. Leaves are
+/// ordered by customer identifier and a node without a sibling is carried up unchanged.
+pub struct SumTree {
+    leaves: Vec<SumLeaf>,
+    levels: Vec<Vec<SumNode>>,
+}
+
+impl SumTree {
+    pub fn new(mut leaves: Vec<SumLeaf>) -> Result<SumTree, &'static str> {
+        if leaves.is_empty() {
+            return Err("sum tree needs at least one liability");
+        }
+        leaves.sort_by(|a, b| a.cid.cmp(&b.cid));
+
+        let nodes = leaves
+            .iter()
+            .map(|leaf| SumNode::leaf(&leaf.cid, leaf.balance))
+            .collect();
+        let levels = build_levels(nodes, SumNode::parent).ok_or("total balance overflow")?;
+
+        Ok(SumTree { leaves, levels })
+    }
+
+    /// The published root. Its sum is the total liabilities.
+    pub fn root(&self) -> &SumNode {
+        &self.levels[self.levels.len() - 1][0]
+    }
+
+    pub fn leaves(&self) -> &[SumLeaf] {
+        &self.leaves
+    }
+
+    /// Inclusion proof to hand to the customer with identifier `cid`, along with their leaf
+    pub fn prove(&self, cid: &[u8; 32]) -> Option<SumTreeProof> {
+        let index = self
+            .leaves
+            .binary_search_by(|leaf| leaf.cid.cmp(cid))
+            .ok()?;
+
+        Some(SumTreeProof {
+            index,
+            leaves: self.leaves.len(),
+            n: self.leaves[index].n.clone(),
+            siblings: siblings(&self.levels, index),
+        })
+    }
+}
+
+/// A customer's inclusion proof in a `SumTree`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SumTreeProof {
+    /// Position of the leaf in the tree
+    pub index: usize,
+    /// Number of leaves in the tree
+    pub leaves: usize,
+    /// Customer Identifier Salt
+    n: BigUint,
+    /// Sibling nodes from the leaf up, skipping levels where the node has no sibling
+    siblings: Vec<SumNode>,
+}
+
+impl SumTreeProof {
+    /// Customer verification that their balance is counted in the total at `root`
+    pub fn verify_as_customer(&self, identifier: &[u8], balance: u64, root: &SumNode) -> bool {
+        let computed = path_root(
+            SumNode::leaf(&compute_cid(identifier, &self.n), balance),
+            self.index,
+            self.leaves,
+            &self.siblings,
+            SumNode::parent,
+        );
+        computed.as_ref() == Some(root)
+    }
+}
+
+impl Serialize for SumTreeProof {
+    /// Encodes into 8 + 8 + 32 + 40 * path length bytes
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = vec![];
+        out.extend(&(self.index as u64).to_be_bytes());
+        out.extend(&(self.leaves as u64).to_be_bytes());
+        out.extend(biguint_to_bytes_be(&self.n, 32));
+        for sibling in self.siblings.iter() {
+            out.extend(sibling.serialize());
+        }
+        out
+    }
+}
+
+impl Deserialize for SumTreeProof {
+    fn deserialize(bytes: &[u8]) -> SumTreeProof {
+        let mut index = [0; 8];
+        index.copy_from_slice(&bytes[0..8]);
+        let mut leaves = [0; 8];
+        leaves.copy_from_slice(&bytes[8..16]);
+
+        SumTreeProof {
+            index: u64::from_be_bytes(index) as usize,
+            leaves: u64::from_be_bytes(leaves) as usize,
+            n: BigUint::from_bytes_be(&bytes[16..48]),
+            siblings: bytes[48..].chunks(40).map(SumNode::deserialize).collect(),
+        }
+    }
+}
+
+/// Side by side comparison of publishing liabilities as a `SumTree` or as a `LiabilityProof` per
+/// customer with a `CommitmentTree`, for the same number of customers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LiabilityModeReport {
+    pub customers: usize,
+    /// Total liabilities, disclosed by the root of the sum tree
+    pub total: u64,
+    /// Bytes published for the whole sum tree
+    pub sum_tree_bytes: usize,
+    /// Bytes of the largest customer inclusion proof in the sum tree
+    pub sum_tree_proof_bytes: usize,
+    /// Bytes published for every customer's liability proof
+    pub liability_proof_bytes: usize,
+    /// Bytes of the largest customer path in the commitment tree
+    pub commitment_path_bytes: usize,
+    /// Bits the liability proofs prove each balance fits in
+    pub balance_bits: usize,
+}
+
+impl LiabilityModeReport {
+    pub fn new(tree: &SumTree, params: &Params) -> LiabilityModeReport {
+        let nodes: usize = tree.levels.iter().map(|level| level.len()).sum();
+        let depth = tree.levels.len() - 1;
+
+        LiabilityModeReport {
+            customers: tree.leaves.len(),
+            total: tree.root().sum,
+            sum_tree_bytes: nodes * 40,
+            sum_tree_proof_bytes: 8 + 8 + 32 + depth * 40,
+            liability_proof_bytes: tree.leaves.len() * LiabilityProof::public_encoded_len(params),
+            commitment_path_bytes: 8 + 8 + depth * 65,
+            balance_bits: params.balance_bits,
+        }
+    }
+}
+
+impl fmt::Display for LiabilityModeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Liability modes for {} customers", self.customers)?;
+        writeln!(f)?;
+        writeln!(f, "| | Merkle sum tree | Liability proofs |")?;
+        writeln!(f, "| --- | --- | --- |")?;
+        writeln!(
+            f,
+            "| Published size | {} bytes | {} bytes |",
+            self.sum_tree_bytes, self.liability_proof_bytes
+        )?;
+        writeln!(
+            f,
+            "| Customer proof size | {} bytes | {} bytes |",
+            self.sum_tree_proof_bytes, self.commitment_path_bytes
+        )?;
+        writeln!(
+            f,
+            "| Total liabilities | Disclosed ({}) | Hidden in a commitment |",
+            self.total
+        )?;
+        writeln!(
+            f,
+            "| Other balances | Sibling subtotals disclosed to each customer | Hidden |"
+        )?;
+        writeln!(
+            f,
+            "| Balance range | Unchecked, sums only | Proven to fit in {} bits |",
+            self.balance_bits
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sum_tree_root_is_total_liabilities() {
+        let tree = SumTree::new(gen_leaves(5)).unwrap();
+
+        assert_eq!(tree.root().sum, 10 + 11 + 12 + 13 + 14);
+    }
+
+    #[test]
+    fn sum_tree_proofs_verify_for_every_customer() {
+        let tree = SumTree::new(gen_leaves(5)).unwrap();
+
+        for i in 0..5u64 {
+            let id = format!("user{}", i);
+            let cid = compute_cid(id.as_bytes(), &BigUint::from(i + 100));
+            let proof = tree.prove(&cid).unwrap();
+            let proof = SumTreeProof::deserialize(&proof.serialize());
+
+            assert!(proof.verify_as_customer(id.as_bytes(), 10 + i, tree.root()));
+            assert!(!proof.verify_as_customer(id.as_bytes(), 9 + i, tree.root()));
+        }
+    }
+
+    #[test]
+    fn sum_tree_report_compares_modes() {
+        let tree = SumTree::new(gen_leaves(4)).unwrap();
+        let params = Params::default().balance_bits(16);
+        let report = LiabilityModeReport::new(&tree, &params);
+
+        assert_eq!(report.customers, 4);
+        assert_eq!(report.total, 46);
+        assert_eq!(report.sum_tree_bytes, 7 * 40);
+        assert_eq!(report.sum_tree_proof_bytes, 48 + 2 * 40);
+        assert!(report.to_string().contains("Disclosed (46)"));
+        assert!(report.to_string().contains("fit in 16 bits"));
+    }
+
+    fn gen_leaves(num: u64) -> Vec<SumLeaf> {
+        (0..num)
+            .map(|i| {
+                let id = format!("user{}", i);
+                let n = BigUint::from(i + 100);
+                SumLeaf::new(id.as_bytes(), &BigUint::from(10 + i), n).unwrap()
+            })
+            .collect()
+    }
+}
@@ -1,10 +1,14 @@
 use crate::bigint::biguint_to_bytes_be;
 use crate::fields::field_sqrt;
 use crate::fields::Field256;
+#[cfg(feature = "json")]
+use crate::json::{from_hex, FromJson, ToJson};
 use crate::serialization::{Deserialize, Serialize};
 use num_bigint::BigUint;
 use secp256k1::constants::{GENERATOR_X, GENERATOR_Y};
 use secp256k1::{All, Error, PublicKey, Secp256k1};
+#[cfg(feature = "json")]
+use serde_json::Value;
 use sha2::{Digest, Sha256};
 use std::fmt;
 
@@ -27,10 +31,15 @@ pub fn field_order() -> BigUint {
     BigUint::parse_bytes(hex, 16).unwrap()
 }
 
-#[derive(Clone, PartialEq, Debug)]
+/// A point on the curve or the point at infinity, the identity of the group.
+///
+/// The identity is encoded as all zero bytes, 33 compressed or 65 uncompressed, which never
+/// encodes a point on the curve.
+#[derive(Clone, Debug)]
 pub struct Point {
     pk: PublicKey,
     secp256k1: Secp256k1<All>,
+    /// When set `pk` is meaningless
     infinity: bool,
 }
 
@@ -46,7 +55,27 @@ impl Point {
         out
     }
 
+    pub fn is_infinity(&self) -> bool {
+        self.infinity
+    }
+
+    /// Read a 33 byte compressed point, failing rather than panicking on invalid input
+    pub fn try_deserialize(bytes: &[u8]) -> Result<Point, &'static str> {
+        if bytes.len() != 33 {
+            return Err("point must be 33 bytes");
+        }
+        if bytes.iter().all(|b| *b == 0) {
+            return Ok(Point::infinity());
+        }
+        let pk = PublicKey::from_slice(bytes).map_err(|_| "invalid point")?;
+        Ok(Point::from(pk))
+    }
+
+    /// Serialize into 65 bytes, all zero for the point at infinity
     pub fn serialize_uncompressed(&self) -> [u8; 65] {
+        if self.infinity {
+            return [0; 65];
+        }
         self.pk.serialize_uncompressed()
     }
 
@@ -76,9 +105,11 @@ impl Point {
             // P + O = P
             // Noop
         } else {
-            // P + Q = R
-            self.pk = self.pk.combine(&other.pk).expect("invalid addition");
-            self.infinity = false;
+            // P + Q = R, the library refuses to return P + -P = O
+            match self.pk.combine(&other.pk) {
+                Ok(pk) => self.pk = pk,
+                Err(_) => self.infinity = true,
+            }
         }
         self
     }
@@ -90,16 +121,19 @@ impl Point {
 
     /// Return the additive inverse of the point. -P where P + -P = 0
     pub fn inverse(&self) -> Point {
+        if self.infinity {
+            return self.clone();
+        }
+
         // The secp256k1 library doesn't provide raw access to the coordinates or allow
         // initializing from them directly. So to flip the y coordinate we need to serialize it,
         // parse the y coordinate and flip it, update the serialized version, an initialize a new
-        // point.
+        // point. y is a coordinate, so it is negated modulo the field prime p, never zero on
+        // this curve.
         let mut sec = self.serialize_uncompressed();
-        let y = Field256::from_bytes_be(&sec[33..]);
-        let y_inv = (-y).to_bytes_be();
-        for i in 0..y_inv.len() {
-            sec[33 + i] = y_inv[i];
-        }
+        let y = BigUint::from_bytes_be(&sec[33..]);
+        let y_inv = biguint_to_bytes_be(&(field_order() - y), 32);
+        sec[33..].copy_from_slice(&y_inv);
         let new_point = PublicKey::from_slice(&sec).expect("point to be valid");
 
         Point {
@@ -109,23 +143,58 @@ impl Point {
         }
     }
 
-    /// Hash arbitrary content into a point on the curve.
+    /// Hash arbitrary content into a point on the curve with try-and-increment.
     ///
-    /// This is done SHA256 hashing the content into a number and using that as x.
-    /// Then we solve for y s.t y = x^3 + 7.
+    /// The SHA256 hash of the content is used as x and we solve for y s.t y^2 = x^3 + 7. When x
+    /// isn't below p or x^3 + 7 has no square root the content followed by a counter byte is
+    /// hashed instead, counting from 1. Half the candidates work, so failing all 256 attempts
+    /// doesn't happen in practice. Nobody knows the discrete log of the result with respect to g.
     pub fn from_hash(content: &[u8]) -> Result<Point, Error> {
-        let q = field_order();
-        let x = BigUint::from_bytes_be(Sha256::digest(content).as_slice());
-        let rhs = x.modpow(&BigUint::from(3u8), &q) + BigUint::from(7u8);
-        let y = field_sqrt(&rhs, &q);
+        for counter in 0..=255u8 {
+            let mut hasher = Sha256::new();
+            hasher.input(content);
+            if counter > 0 {
+                hasher.input(&[counter]);
+            }
+            if let Some(point) = Point::from_x(&hasher.result()) {
+                return Ok(point);
+            }
+        }
+        Err(Error::InvalidPublicKey)
+    }
+
+    /// Independent generators h_0, ..., h_(count - 1) derived from a domain label, e.g. one per
+    /// asset for multi-asset or vector commitments
+    pub fn generators(domain: &[u8], count: usize) -> Vec<Point> {
+        (0..count as u32)
+            .map(|i| {
+                let mut content = b"provisions/generator".to_vec();
+                content.extend(&(domain.len() as u64).to_be_bytes());
+                content.extend(domain);
+                content.extend(&i.to_be_bytes());
+                Point::from_hash(&content).expect("hash to curve failed")
+            })
+            .collect()
+    }
+
+    /// The point with the 32 byte big-endian x coordinate, if there is one
+    fn from_x(x_bytes: &[u8]) -> Option<Point> {
+        let p = field_order();
+        let x = BigUint::from_bytes_be(x_bytes);
+        if x >= p {
+            return None;
+        }
+        let rhs = (x.modpow(&BigUint::from(3u8), &p) + BigUint::from(7u8)) % &p;
+        let y = field_sqrt(&rhs, &p);
+        if (&y * &y) % &p != rhs {
+            return None;
+        }
 
         let mut g_bytes = Vec::with_capacity(65);
         g_bytes.push(0x04);
         g_bytes.extend_from_slice(&biguint_to_bytes_be(&x, 32));
         g_bytes.extend_from_slice(&biguint_to_bytes_be(&y, 32));
-        let g = PublicKey::from_slice(&g_bytes)?;
-
-        Ok(Point::from(g))
+        PublicKey::from_slice(&g_bytes).ok().map(Point::from)
     }
 }
 
@@ -152,18 +221,33 @@ impl From<PublicKey> for Point {
     }
 }
 
+impl PartialEq for Point {
+    /// Every point at infinity is equal, whatever was last stored in `pk`
+    fn eq(&self, other: &Point) -> bool {
+        match (self.infinity, other.infinity) {
+            (false, false) => self.pk == other.pk,
+            (infinity, other_infinity) => infinity == other_infinity,
+        }
+    }
+}
+
 impl Serialize for Point {
-    /// Serialize into 33 bytes (compressed)
+    /// Serialize into 33 bytes (compressed), all zero for the point at infinity
     fn serialize(&self) -> Vec<u8> {
         if self.infinity {
-            panic!("Never expected to serialize the point at infinity");
+            return vec![0; 33];
         }
         self.pk.serialize().to_vec()
     }
 }
 
 impl Deserialize for Point {
+    /// Reads a compressed or uncompressed encoding, where all zero bytes are the point at
+    /// infinity
     fn deserialize(bytes: &[u8]) -> Point {
+        if bytes.iter().all(|b| *b == 0) {
+            return Point::infinity();
+        }
         let pk = PublicKey::from_slice(bytes).expect("valid");
 
         Point {
@@ -174,6 +258,21 @@ impl Deserialize for Point {
     }
 }
 
+#[cfg(feature = "json")]
+impl ToJson for Point {
+    /// Hex encoding of the 33 byte compressed serialization
+    fn to_json(&self) -> Value {
+        Value::from(hex::encode(self.serialize()))
+    }
+}
+
+#[cfg(feature = "json")]
+impl FromJson for Point {
+    fn from_json(value: &Value) -> Result<Point, &'static str> {
+        Point::try_deserialize(&from_hex(value)?)
+    }
+}
+
 // Create commitment of y = g^x * h^r
 pub fn pedersen_commitment(g: Point, x: &Field256, h: Point, r: &Field256) -> Point {
     let mut gx = point_mul(g, x);
@@ -241,8 +340,93 @@ mod tests {
         );
     }
 
+    #[test]
+    fn adding_inverse_gives_infinity() {
+        let p = point_mul(Point::g(), &Field256::from(5));
+
+        assert_eq!(point_add(p.clone(), &p.inverse()), Point::infinity());
+        assert_eq!(
+            point_add(p.clone(), &point_inverse(p.clone())),
+            Point::infinity()
+        );
+        assert_eq!(point_add(Point::infinity(), &p), p);
+        assert_eq!(point_add(p.clone(), &Point::infinity()), p);
+    }
+
+    #[test]
+    fn multiplying_by_zero_gives_infinity() {
+        let p = point_mul(Point::g(), &Field256::from(5));
+
+        assert_eq!(point_mul(p.clone(), &Field256::zero()), Point::infinity());
+        assert_eq!(
+            point_mul(Point::infinity(), &Field256::from(3)),
+            Point::infinity()
+        );
+        assert_eq!(
+            pedersen_commitment(Point::g(), &Field256::zero(), p.clone(), &Field256::one()),
+            p
+        );
+    }
+
+    #[test]
+    fn cancelling_sum_gives_infinity() {
+        let p = point_mul(Point::g(), &Field256::from(5));
+        let q = point_mul(Point::g(), &Field256::from(7));
+        let neg_sum = point_mul(Point::g(), &-Field256::from(12));
+
+        let sum = point_sum(&[&p, &q, &neg_sum]);
+
+        assert!(sum.is_infinity());
+        assert_eq!(sum, Point::infinity());
+        assert_eq!(sum.inverse(), Point::infinity());
+        assert_eq!(point_add(sum, &p), p);
+    }
+
+    #[test]
+    fn infinity_serialization() {
+        let zero = point_mul(Point::g(), &Field256::zero());
+
+        assert_eq!(zero.serialize(), vec![0; 33]);
+        assert_eq!(zero.serialize_uncompressed().to_vec(), vec![0; 65]);
+        assert_eq!(Point::deserialize(&zero.serialize()), Point::infinity());
+        assert_eq!(Point::deserialize(&[0; 65]), Point::infinity());
+        assert_ne!(Point::infinity(), Point::g());
+    }
+
     #[test]
     fn secp256k1_from_hash() {
         Point::from_hash(b"PROVISIONS").expect("invalid point produced");
     }
+
+    #[test]
+    fn from_hash_keeps_h() {
+        // The first attempt hashes the content alone
+        let h = Point::from_hash(b"PROVISIONS").unwrap().serialize();
+
+        assert_eq!(h[0], 0x03);
+        assert_eq!(&h[1..], Sha256::digest(b"PROVISIONS").as_slice());
+    }
+
+    #[test]
+    fn from_hash_retries_without_square_root() {
+        // About half of these have no square root on the first attempt
+        for i in 0..32u8 {
+            let point = Point::from_hash(&[i]).expect("invalid point produced");
+            assert!(!point.is_infinity());
+        }
+    }
+
+    #[test]
+    fn generators_are_independent() {
+        let generators = Point::generators(b"assets", 4);
+
+        assert_eq!(generators.len(), 4);
+        assert_eq!(generators, Point::generators(b"assets", 4));
+        assert_ne!(generators[0], Point::generators(b"liabilities", 1)[0]);
+        for (i, generator) in generators.iter().enumerate() {
+            assert_ne!(generator, &Point::g());
+            assert_ne!(generator, &crate::h());
+            assert!(!generators[(i + 1)..].contains(generator));
+        }
+    }
 }
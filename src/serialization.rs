@@ -5,3 +5,17 @@ pub trait Serialize {
 pub trait Deserialize {
     fn deserialize(bytes: &[u8]) -> Self;
 }
+
+/// Encode fixed length records back to back, e.g. to exchange a batch of them as a single file.
+pub fn serialize_all<T: Serialize>(items: &[T]) -> Vec<u8> {
+    items.iter().flat_map(|item| item.serialize()).collect()
+}
+
+/// Decode records written by `serialize_all`, each of which is `len` bytes long.
+pub fn deserialize_all<T: Deserialize>(bytes: &[u8], len: usize) -> Result<Vec<T>, &'static str> {
+    if bytes.len() % len != 0 {
+        return Err("length is not a multiple of the record length");
+    }
+
+    Ok(bytes.chunks(len).map(T::deserialize).collect())
+}
@@ -0,0 +1,267 @@
+use crate::fields::Field256;
+use crate::proofs::{AssetProofRequest, AssetProofResponse};
+use crate::secp256k1::{point_mul, Point};
+use crate::serialization::{Deserialize, Serialize};
+use crate::signer::{respond_with_key, Signer};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use scrypt::{scrypt, ScryptParams};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+const MAGIC: &[u8; 4] = b"PVKS";
+const VERSION: u8 = 1;
+/// magic, version, scrypt log_n, r and p, salt
+const HEADER_BYTES: usize = 4 + 1 + 1 + 4 + 4 + 32;
+const NONCE_BYTES: usize = 12;
+const TAG_BYTES: usize = 16;
+/// public key, nonce, encrypted private key and tag
+const ENTRY_BYTES: usize = 33 + NONCE_BYTES + 32 + TAG_BYTES;
+
+/// Default scrypt cost: N = 2^15, r = 8, p = 1
+const DEFAULT_LOG_N: u8 = 15;
+const DEFAULT_R: u32 = 8;
+const DEFAULT_P: u32 = 1;
+
+/// File based store of private keys encrypted under a passphrase, indexed by public key.
+///
+/// The encryption key is derived from the passphrase with scrypt and every private key is
+/// encrypted separately with ChaCha20-Poly1305, using its public key as associated data so
+/// entries can't be swapped between keys. Private keys are only decrypted when a response is
+/// requested for them. The file is laid out as:
+///
+/// | Bytes | Content |
+/// | --- | --- |
+/// | 4 | Magic `PVKS` |
+/// | 1 | Version |
+/// | 1, 4, 4 | scrypt log_n, r and p (big-endian) |
+/// | 32 | scrypt salt |
+/// | 12 + 16 | Nonce and tag encrypting nothing with the header as associated data |
+/// | 8 | Number of keys (big-endian) |
+/// | 93 each | Compressed public key, nonce and encrypted private key with tag |
+///
+/// The empty ciphertext after the header lets a wrong passphrase be detected when the file is
+/// opened rather than at proof time.
+pub struct Keystore {
+    header: Vec<u8>,
+    key: [u8; 32],
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Keystore {
+    /// Create an empty keystore protected by `passphrase`
+    pub fn create(passphrase: &str) -> Keystore {
+        Keystore::create_with_params(passphrase, DEFAULT_LOG_N, DEFAULT_R, DEFAULT_P)
+    }
+
+    /// Create an empty keystore with explicit scrypt parameters
+    pub fn create_with_params(passphrase: &str, log_n: u8, r: u32, p: u32) -> Keystore {
+        let mut salt = [0; 32];
+        OsRng::new().expect("OsRng").fill_bytes(&mut salt);
+
+        let mut header = Vec::with_capacity(HEADER_BYTES);
+        header.extend(MAGIC);
+        header.push(VERSION);
+        header.push(log_n);
+        header.extend(&r.to_be_bytes());
+        header.extend(&p.to_be_bytes());
+        header.extend(&salt);
+
+        let key = derive_key(passphrase, &header).expect("valid scrypt params");
+
+        Keystore {
+            header,
+            key,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Open the keystore at `path`, failing if the passphrase is wrong
+    pub fn open(path: &str, passphrase: &str) -> io::Result<Keystore> {
+        Keystore::from_bytes(&fs::read(path)?, passphrase)
+    }
+
+    fn from_bytes(bytes: &[u8], passphrase: &str) -> io::Result<Keystore> {
+        let entries_start = HEADER_BYTES + NONCE_BYTES + TAG_BYTES + 8;
+        if bytes.len() < entries_start || &bytes[0..4] != MAGIC || bytes[4] != VERSION {
+            return Err(invalid_data("not a keystore"));
+        }
+
+        let header = bytes[0..HEADER_BYTES].to_vec();
+        let key = derive_key(passphrase, &header).map_err(invalid_data)?;
+        let check = &bytes[HEADER_BYTES..(HEADER_BYTES + NONCE_BYTES + TAG_BYTES)];
+        decrypt(&key, check, &header).map_err(|_| invalid_data("wrong passphrase"))?;
+
+        let mut count = [0; 8];
+        count.copy_from_slice(&bytes[(entries_start - 8)..entries_start]);
+        let count = u64::from_be_bytes(count) as usize;
+        if bytes.len() != entries_start + count * ENTRY_BYTES {
+            return Err(invalid_data("truncated keystore"));
+        }
+
+        let entries = bytes[entries_start..]
+            .chunks(ENTRY_BYTES)
+            .map(|entry| (entry[0..33].to_vec(), entry[33..].to_vec()))
+            .collect();
+
+        Ok(Keystore {
+            header,
+            key,
+            entries,
+        })
+    }
+
+    /// Write the keystore to `path`
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.header.clone();
+        out.extend(encrypt(&self.key, &[], &self.header));
+        out.extend(&(self.entries.len() as u64).to_be_bytes());
+        for (y, encrypted) in self.entries.iter() {
+            out.extend(y);
+            out.extend(encrypted);
+        }
+        out
+    }
+
+    /// Encrypt and store the private key x, returning its public key
+    pub fn insert(&mut self, x: &Field256) -> Point {
+        let y = point_mul(Point::g(), x);
+        let y_bytes = y.serialize();
+        let encrypted = encrypt(&self.key, &x.serialize(), &y_bytes);
+        self.entries.insert(y_bytes, encrypted);
+        y
+    }
+
+    /// Public keys of every private key in the store
+    pub fn public_keys(&self) -> Vec<Point> {
+        self.entries.keys().map(|y| Point::deserialize(y)).collect()
+    }
+
+    /// Decrypt the private key of y
+    fn private_key(&self, y: &Point) -> Result<Field256, &'static str> {
+        let y_bytes = y.serialize();
+        let encrypted = self.entries.get(&y_bytes).ok_or("key not held by signer")?;
+        let x = decrypt(&self.key, encrypted, &y_bytes)?;
+        Ok(Field256::deserialize(&x))
+    }
+}
+
+impl Signer for Keystore {
+    fn has_key(&self, y: &Point) -> bool {
+        self.entries.contains_key(&y.serialize())
+    }
+
+    fn respond(
+        &self,
+        keys: &[Point],
+        request: &AssetProofRequest,
+        g: &Point,
+        h: &Point,
+    ) -> Result<AssetProofResponse, &'static str> {
+        let mut x = Field256::zero();
+        for key in keys.iter() {
+            x = x + self.private_key(key)?;
+        }
+
+        respond_with_key(&x, request, g, h)
+    }
+}
+
+/// Derive the encryption key from the passphrase and the scrypt parameters in the header
+fn derive_key(passphrase: &str, header: &[u8]) -> Result<[u8; 32], &'static str> {
+    let mut r = [0; 4];
+    r.copy_from_slice(&header[6..10]);
+    let mut p = [0; 4];
+    p.copy_from_slice(&header[10..14]);
+    let params = ScryptParams::new(header[5], u32::from_be_bytes(r), u32::from_be_bytes(p))
+        .map_err(|_| "invalid scrypt parameters")?;
+
+    let mut key = [0; 32];
+    scrypt(passphrase.as_bytes(), &header[14..46], &params, &mut key).expect("valid length");
+    Ok(key)
+}
+
+/// Encrypt under a fresh random nonce, returning nonce || ciphertext || tag
+fn encrypt(key: &[u8; 32], msg: &[u8], aad: &[u8]) -> Vec<u8> {
+    let mut nonce = [0; NONCE_BYTES];
+    OsRng::new().expect("OsRng").fill_bytes(&mut nonce);
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg, aad })
+        .expect("encryption works");
+
+    let mut out = nonce.to_vec();
+    out.extend(ciphertext);
+    out
+}
+
+/// Decrypt the output of `encrypt`
+fn decrypt(key: &[u8; 32], bytes: &[u8], aad: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let (nonce, msg) = bytes.split_at(NONCE_BYTES);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), Payload { msg, aad })
+        .map_err(|_| "unable to decrypt key")
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proofs::PendingAssetProof;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn keystore_round_trips_through_file() {
+        let (g, h) = (crate::g(), crate::h());
+        let mut keystore = Keystore::create_with_params("hunter2", 4, 8, 1);
+        let y = keystore.insert(&Field256::from(7));
+        let path = std::env::temp_dir().join("provisions_keystore_round_trip");
+        let path = path.to_str().unwrap();
+        keystore.save(path).unwrap();
+
+        let keystore = Keystore::open(path, "hunter2").unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(keystore.public_keys(), vec![y.clone()]);
+
+        let pending = PendingAssetProof::prepare(&y, BigUint::from(10u8), true, &g, &h);
+        let response = keystore
+            .respond(&[y.clone()], pending.request(), &g, &h)
+            .unwrap();
+        let proof = pending.finalize(response).unwrap();
+
+        assert!(proof.verify(), "proof not able to be verified");
+    }
+
+    #[test]
+    fn keystore_rejects_wrong_passphrase() {
+        let mut keystore = Keystore::create_with_params("hunter2", 4, 8, 1);
+        keystore.insert(&Field256::from(7));
+        let bytes = keystore.to_bytes();
+
+        assert!(Keystore::from_bytes(&bytes, "hunter3").is_err());
+    }
+
+    #[test]
+    fn keystore_rejects_swapped_entries() {
+        let mut keystore = Keystore::create_with_params("hunter2", 4, 8, 1);
+        let y1 = keystore.insert(&Field256::from(7));
+        let y2 = keystore.insert(&Field256::from(8));
+        let encrypted = keystore.entries[&y1.serialize()].clone();
+        keystore.entries.insert(y2.serialize(), encrypted);
+
+        assert!(keystore.private_key(&y1).is_ok());
+        assert!(keystore.private_key(&y2).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use crate::fields::Field256;
+use crate::proofs::{AssetProofRequest, AssetProofResponse};
+use crate::secp256k1::{point_mul, Point};
+use crate::serialization::Serialize;
+use std::collections::HashMap;
+
+#[cfg(feature = "keystore")]
+mod keystore;
+
+#[cfg(feature = "keystore")]
+pub use self::keystore::Keystore;
+
+/// Holds the private keys of the exchange and performs the private key dependent step of the
+/// asset proof on request, so the rest of proof generation never handles private keys.
+pub trait Signer: Sync {
+    /// Whether the signer holds the private key of y
+    fn has_key(&self, y: &Point) -> bool;
+
+    /// Respond to a request for the key that is the sum of `keys`, all of which the signer must
+    /// hold. For a single key proof `keys` is just the key of the request.
+    fn respond(
+        &self,
+        keys: &[Point],
+        request: &AssetProofRequest,
+        g: &Point,
+        h: &Point,
+    ) -> Result<AssetProofResponse, &'static str>;
+}
+
+/// Signer with the private keys held in memory, indexed by public key.
+pub struct MemorySigner {
+    keys: HashMap<Vec<u8>, Field256>,
+}
+
+impl MemorySigner {
+    pub fn new(keys: Vec<Field256>) -> MemorySigner {
+        let keys = keys
+            .into_iter()
+            .map(|x| (point_mul(Point::g(), &x).serialize(), x))
+            .collect();
+
+        MemorySigner { keys }
+    }
+}
+
+impl Signer for MemorySigner {
+    fn has_key(&self, y: &Point) -> bool {
+        self.keys.contains_key(&y.serialize())
+    }
+
+    fn respond(
+        &self,
+        keys: &[Point],
+        request: &AssetProofRequest,
+        g: &Point,
+        h: &Point,
+    ) -> Result<AssetProofResponse, &'static str> {
+        let mut x = Field256::zero();
+        for key in keys.iter() {
+            match self.keys.get(&key.serialize()) {
+                None => return Err("key not held by signer"),
+                Some(x_i) => x = x + x_i,
+            }
+        }
+
+        respond_with_key(&x, request, g, h)
+    }
+}
+
+/// Respond to `request` with x after checking it is the private key of the requested key, so a
+/// signer never answers a request for a key it wasn't asked about.
+fn respond_with_key(
+    x: &Field256,
+    request: &AssetProofRequest,
+    g: &Point,
+    h: &Point,
+) -> Result<AssetProofResponse, &'static str> {
+    if point_mul(g.clone(), x) != request.y {
+        return Err("keys do not match request");
+    }
+
+    Ok(request.respond(x, g, h))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proofs::{AssetProof, PendingAssetProof};
+    use num_bigint::BigUint;
+
+    #[test]
+    fn memory_signer_responds_for_held_keys() {
+        let (g, h) = (crate::g(), crate::h());
+        let signer = MemorySigner::new(vec![Field256::from(5)]);
+        let y = point_mul(g.clone(), &Field256::from(5));
+        assert!(signer.has_key(&y));
+
+        let pending = PendingAssetProof::prepare(&y, BigUint::from(10u8), true, &g, &h);
+        let response = signer
+            .respond(&[y.clone()], pending.request(), &g, &h)
+            .unwrap();
+        let proof: AssetProof = pending.finalize(response).unwrap();
+
+        assert!(proof.verify(), "proof not able to be verified");
+    }
+
+    #[test]
+    fn memory_signer_refuses_unknown_keys() {
+        let (g, h) = (crate::g(), crate::h());
+        let signer = MemorySigner::new(vec![Field256::from(5)]);
+        let y = point_mul(g.clone(), &Field256::from(6));
+        assert!(!signer.has_key(&y));
+
+        let pending = PendingAssetProof::prepare(&y, BigUint::from(10u8), true, &g, &h);
+
+        assert!(signer.respond(&[y], pending.request(), &g, &h).is_err());
+    }
+}
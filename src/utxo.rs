@@ -0,0 +1,670 @@
+use crate::fields::Field256;
+use crate::params::Params;
+use crate::proofs::{AssetProof, MultisigAssetProof};
+use crate::secp256k1::{point_mul, Point};
+use crate::serialization::Serialize;
+use num_bigint::BigUint;
+use num_traits::Zero;
+use ripemd160::Ripemd160;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+
+/// Magic bytes at the start of every snapshot file
+const SNAPSHOT_MAGIC: [u8; 5] = [b'u', b't', b'x', b'o', 0xff];
+/// The snapshot format version this reader understands
+const SNAPSHOT_VERSION: u16 = 2;
+/// Number of script types with a special compressed encoding
+const SPECIAL_SCRIPTS: u64 = 6;
+/// Scripts larger than this are unspendable and replaced by the node when loading
+const MAX_SCRIPT_SIZE: u64 = 10_000;
+/// Largest number of keys of a standard multisig script
+const MAX_MULTISIG_KEYS: usize = 16;
+
+/// Metadata at the start of a UTXO snapshot
+#[derive(Debug, PartialEq)]
+pub struct SnapshotHeader {
+    /// Network message start bytes of the chain the snapshot was taken from
+    pub network_magic: [u8; 4],
+    /// Hash of the block the snapshot was taken at
+    pub base_blockhash: [u8; 32],
+    /// Number of unspent outputs in the snapshot
+    pub coins_count: u64,
+}
+
+/// A published asset proof whose balance commitment disagrees with the UTXO snapshot
+#[derive(Debug, PartialEq)]
+pub enum BalanceMismatch {
+    /// b is not g raised to the balance of y in the snapshot
+    Incorrect { y: Point, snapshot_balance: BigUint },
+    /// y has no outputs in the snapshot
+    Missing { y: Point },
+    /// b is not g raised to the balance of the multisig script over keys in the snapshot
+    MultisigIncorrect {
+        keys: Vec<Point>,
+        threshold: usize,
+        snapshot_balance: BigUint,
+    },
+    /// The multisig script over keys has no outputs in the snapshot
+    MultisigMissing { keys: Vec<Point>, threshold: usize },
+}
+
+/// Reader for the UTXO set dump written by Bitcoin Core's `dumptxoutset` RPC (snapshot format
+/// version 2, Bitcoin Core 28 and later).
+///
+/// Only the output scripts are inspected. P2PK outputs are matched on the public key and P2PKH
+/// and P2WPKH outputs on the hash of its compressed or uncompressed encoding. Multisig outputs
+/// are matched on the standard m-of-n script over the keys in the order given, bare or behind
+/// P2SH, P2WSH or P2SH-P2WSH. Every other output type is skipped.
+pub struct SnapshotReader<R> {
+    reader: R,
+    header: SnapshotHeader,
+}
+
+impl SnapshotReader<BufReader<File>> {
+    /// Open the snapshot file at `path` and read its header
+    pub fn open(path: &str) -> io::Result<SnapshotReader<BufReader<File>>> {
+        let file = File::open(path)?;
+        SnapshotReader::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read> SnapshotReader<R> {
+    pub fn new(mut reader: R) -> io::Result<SnapshotReader<R>> {
+        let mut magic = [0; 5];
+        reader.read_exact(&mut magic)?;
+        if magic != SNAPSHOT_MAGIC {
+            return Err(invalid_data("not a utxo snapshot"));
+        }
+
+        let mut version = [0; 2];
+        reader.read_exact(&mut version)?;
+        if u16::from_le_bytes(version) != SNAPSHOT_VERSION {
+            return Err(invalid_data("unsupported utxo snapshot version"));
+        }
+
+        let mut network_magic = [0; 4];
+        reader.read_exact(&mut network_magic)?;
+        let mut base_blockhash = [0; 32];
+        reader.read_exact(&mut base_blockhash)?;
+        let mut coins_count = [0; 8];
+        reader.read_exact(&mut coins_count)?;
+
+        let header = SnapshotHeader {
+            network_magic,
+            base_blockhash,
+            coins_count: u64::from_le_bytes(coins_count),
+        };
+
+        Ok(SnapshotReader { reader, header })
+    }
+
+    pub fn header(&self) -> &SnapshotHeader {
+        &self.header
+    }
+
+    /// Sum the value of every output spendable by one of `keys`. The result is in the same order
+    /// as `keys` and leaves out keys that don't have any outputs in the snapshot.
+    pub fn balances(self, keys: &[Point]) -> io::Result<Vec<(Point, BigUint)>> {
+        let totals = self.totals(&KeyIndex::new(keys, &[]), keys.len())?;
+
+        Ok(keys
+            .iter()
+            .zip(totals.into_iter())
+            .filter_map(|(key, total)| total.map(|total| (key.clone(), total)))
+            .collect())
+    }
+
+    /// Sum the value of every output matched by `index`, by position in the index
+    fn totals(mut self, index: &KeyIndex, len: usize) -> io::Result<Vec<Option<BigUint>>> {
+        let mut totals: Vec<Option<BigUint>> = vec![None; len];
+
+        let mut coins_read = 0;
+        while coins_read < self.header.coins_count {
+            // Outputs are grouped by the transaction that created them
+            let mut txid = [0; 32];
+            self.reader.read_exact(&mut txid)?;
+            let outputs = read_compact_size(&mut self.reader)?;
+
+            for _ in 0..outputs {
+                let _vout = read_compact_size(&mut self.reader)?;
+                let (amount, script) = self.read_coin()?;
+
+                if let Some(i) = index.find(&script) {
+                    let total = totals[i].get_or_insert_with(BigUint::zero);
+                    *total += amount;
+                }
+            }
+            coins_read += outputs;
+        }
+
+        Ok(totals)
+    }
+
+    /// Check that every asset proof and multisig asset proof commits to the balance its key or
+    /// multisig script holds in the snapshot, that is b = g^bal for the g of `params`. The asset
+    /// proof itself only shows that the prover knows the discrete log of b when it owns y, so
+    /// without this check an exchange could inflate its assets.
+    pub fn verify_asset_balances(
+        self,
+        params: &Params,
+        proofs: &[AssetProof],
+        multisig_proofs: &[MultisigAssetProof],
+    ) -> io::Result<Vec<BalanceMismatch>> {
+        let keys: Vec<Point> = proofs.iter().map(|proof| proof.y.clone()).collect();
+        let scripts: Vec<Vec<u8>> = multisig_proofs
+            .iter()
+            .filter_map(|proof| multisig_script(&proof.keys, proof.threshold))
+            .collect();
+        let totals = self.totals(&KeyIndex::new(&keys, &scripts), keys.len() + scripts.len())?;
+
+        // Looked up by encoding, so proofs repeating a key or script all find its total
+        let encodings = keys.iter().map(Point::serialize).chain(scripts);
+        let balances: HashMap<Vec<u8>, BigUint> = encodings
+            .zip(totals)
+            .filter_map(|(encoding, total)| total.map(|total| (encoding, total)))
+            .collect();
+
+        let g = &params.g;
+        let commits_to =
+            |bal: &BigUint, b: &Point| &point_mul(g.clone(), &Field256::new(bal.clone())) == b;
+        let mut mismatches = vec![];
+        for proof in proofs {
+            match balances.get(&proof.y.serialize()) {
+                None => mismatches.push(BalanceMismatch::Missing { y: proof.y.clone() }),
+                Some(bal) if !commits_to(bal, proof.b_ref()) => {
+                    mismatches.push(BalanceMismatch::Incorrect {
+                        y: proof.y.clone(),
+                        snapshot_balance: bal.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        for proof in multisig_proofs {
+            let balance = multisig_script(&proof.keys, proof.threshold)
+                .and_then(|script| balances.get(&script));
+            match balance {
+                None => mismatches.push(BalanceMismatch::MultisigMissing {
+                    keys: proof.keys.clone(),
+                    threshold: proof.threshold,
+                }),
+                Some(bal) if !commits_to(bal, proof.b_ref()) => {
+                    mismatches.push(BalanceMismatch::MultisigIncorrect {
+                        keys: proof.keys.clone(),
+                        threshold: proof.threshold,
+                        snapshot_balance: bal.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        Ok(mismatches)
+    }
+
+    /// Read a coin, returning its value in satoshis and output script
+    fn read_coin(&mut self) -> io::Result<(u64, Script)> {
+        // Height and coinbase flag
+        let _code = read_varint(&mut self.reader)?;
+        let amount = decompress_amount(read_varint(&mut self.reader)?);
+
+        let size = read_varint(&mut self.reader)?;
+        let script = match size {
+            0 => Script::PubKeyHash(read_array(&mut self.reader)?),
+            1 => Script::RedeemScriptHash(read_array(&mut self.reader)?),
+            2 | 3 => {
+                let mut pk = vec![size as u8];
+                pk.extend(&read_bytes(&mut self.reader, 32)?);
+                Script::PubKey(pk)
+            }
+            4 | 5 => {
+                // Uncompressed key stored as its x coordinate, the size carries the parity of y
+                let mut pk = vec![size as u8 - 2];
+                pk.extend(&read_bytes(&mut self.reader, 32)?);
+                Script::PubKey(pk)
+            }
+            _ => {
+                let len = size - SPECIAL_SCRIPTS;
+                if len <= MAX_SCRIPT_SIZE {
+                    Script::raw(read_bytes(&mut self.reader, len as usize)?)
+                } else {
+                    // Unspendable like in Bitcoin Core, skipped without buffering the script
+                    skip_bytes(&mut self.reader, len)?;
+                    Script::Other(vec![])
+                }
+            }
+        };
+
+        Ok((amount, script))
+    }
+}
+
+/// The parts of an output script needed to match it to a public key
+enum Script {
+    /// Compressed public key of a P2PK output
+    PubKey(Vec<u8>),
+    /// Key hash of a P2PKH output
+    PubKeyHash([u8; 20]),
+    /// Key hash of a P2WPKH output
+    WitnessPubKeyHash([u8; 20]),
+    /// Script hash of a P2SH output
+    RedeemScriptHash([u8; 20]),
+    /// Script hash of a P2WSH output
+    WitnessScriptHash([u8; 32]),
+    Other(Vec<u8>),
+}
+
+impl Script {
+    fn raw(bytes: Vec<u8>) -> Script {
+        // OP_0 OP_PUSHBYTES_20 <key hash>
+        if bytes.len() == 22 && bytes[0] == 0x00 && bytes[1] == 0x14 {
+            let mut hash = [0; 20];
+            hash.copy_from_slice(&bytes[2..]);
+            Script::WitnessPubKeyHash(hash)
+        } else if bytes.len() == 34 && bytes[0] == 0x00 && bytes[1] == 0x20 {
+            // OP_0 OP_PUSHBYTES_32 <script hash>
+            let mut hash = [0; 32];
+            hash.copy_from_slice(&bytes[2..]);
+            Script::WitnessScriptHash(hash)
+        } else {
+            Script::Other(bytes)
+        }
+    }
+}
+
+/// Lookup from the encodings that appear in output scripts to the position of the key, or of
+/// the multisig script after the keys
+struct KeyIndex {
+    compressed: HashMap<Vec<u8>, usize>,
+    compressed_hashes: HashMap<[u8; 20], usize>,
+    uncompressed_hashes: HashMap<[u8; 20], usize>,
+    scripts: HashMap<Vec<u8>, usize>,
+    script_hashes: HashMap<[u8; 20], usize>,
+    witness_script_hashes: HashMap<[u8; 32], usize>,
+}
+
+impl KeyIndex {
+    fn new(keys: &[Point], scripts: &[Vec<u8>]) -> KeyIndex {
+        let mut index = KeyIndex {
+            compressed: HashMap::new(),
+            compressed_hashes: HashMap::new(),
+            uncompressed_hashes: HashMap::new(),
+            scripts: HashMap::new(),
+            script_hashes: HashMap::new(),
+            witness_script_hashes: HashMap::new(),
+        };
+
+        for (i, key) in keys.iter().enumerate() {
+            let compressed = key.serialize();
+            index.compressed_hashes.insert(hash160(&compressed), i);
+            index.compressed.insert(compressed, i);
+            index
+                .uncompressed_hashes
+                .insert(hash160(&key.serialize_uncompressed()), i);
+        }
+        for (i, script) in scripts.iter().enumerate() {
+            let i = keys.len() + i;
+            let witness_hash = sha256(script);
+            let mut witness_program = vec![0x00, 0x20];
+            witness_program.extend(&witness_hash);
+
+            index.script_hashes.insert(hash160(script), i);
+            index.script_hashes.insert(hash160(&witness_program), i);
+            index.witness_script_hashes.insert(witness_hash, i);
+            index.scripts.insert(script.clone(), i);
+        }
+
+        index
+    }
+
+    fn find(&self, script: &Script) -> Option<usize> {
+        let found = match script {
+            Script::PubKey(pk) => self.compressed.get(pk),
+            Script::PubKeyHash(hash) => self
+                .compressed_hashes
+                .get(hash)
+                .or_else(|| self.uncompressed_hashes.get(hash)),
+            Script::WitnessPubKeyHash(hash) => self.compressed_hashes.get(hash),
+            Script::RedeemScriptHash(hash) => self.script_hashes.get(hash),
+            Script::WitnessScriptHash(hash) => self.witness_script_hashes.get(hash),
+            Script::Other(script) => self.scripts.get(script),
+        };
+        found.cloned()
+    }
+}
+
+/// Standard m-of-n multisig script OP_m <keys> OP_n OP_CHECKMULTISIG, or None for more than 16
+/// keys or an invalid threshold
+fn multisig_script(keys: &[Point], threshold: usize) -> Option<Vec<u8>> {
+    if threshold == 0 || threshold > keys.len() || keys.len() > MAX_MULTISIG_KEYS {
+        return None;
+    }
+
+    // OP_1 is 0x51 through OP_16 at 0x60
+    let mut script = vec![0x50 + threshold as u8];
+    for key in keys {
+        script.push(33);
+        script.extend(key.serialize());
+    }
+    script.push(0x50 + keys.len() as u8);
+    // OP_CHECKMULTISIG
+    script.push(0xae);
+    Some(script)
+}
+
+/// RIPEMD160(SHA256(data))
+fn hash160(data: &[u8]) -> [u8; 20] {
+    let digest = Ripemd160::digest(&Sha256::digest(data));
+    let mut out = [0; 20];
+    out.copy_from_slice(&digest[..]);
+    out
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut out = [0; 32];
+    out.copy_from_slice(&Sha256::digest(data)[..]);
+    out
+}
+
+/// Inverse of the amount compression used for coins in the UTXO set
+fn decompress_amount(x: u64) -> u64 {
+    if x == 0 {
+        return 0;
+    }
+
+    let mut x = x - 1;
+    // x = 10 * (9 * n + d - 1) + e
+    let mut e = x % 10;
+    x /= 10;
+
+    let mut n = if e < 9 {
+        let d = (x % 9) + 1;
+        x /= 9;
+        x * 10 + d
+    } else {
+        x + 1
+    };
+
+    while e > 0 {
+        n *= 10;
+        e -= 1;
+    }
+
+    n
+}
+
+/// Read Bitcoin Core's variable length integer encoding: base 128, most significant group first,
+/// with one subtracted from every group but the last.
+fn read_varint<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut n: u64 = 0;
+    loop {
+        let byte = read_array::<R, [u8; 1]>(reader)?[0];
+        if n > (u64::max_value() >> 7) {
+            return Err(invalid_data("varint too large"));
+        }
+        n = (n << 7) | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+        n += 1;
+    }
+}
+
+/// Read a Bitcoin CompactSize length prefix
+fn read_compact_size<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let first = read_array::<R, [u8; 1]>(reader)?[0];
+    match first {
+        253 => Ok(u64::from(u16::from_le_bytes(read_array(reader)?))),
+        254 => Ok(u64::from(u32::from_le_bytes(read_array(reader)?))),
+        255 => Ok(u64::from_le_bytes(read_array(reader)?)),
+        n => Ok(u64::from(n)),
+    }
+}
+
+fn read_array<R: Read, A: Default + AsMut<[u8]>>(reader: &mut R) -> io::Result<A> {
+    let mut out = A::default();
+    reader.read_exact(out.as_mut())?;
+    Ok(out)
+}
+
+fn read_bytes<R: Read>(reader: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut out = vec![0; len];
+    reader.read_exact(&mut out)?;
+    Ok(out)
+}
+
+/// Read and drop `len` bytes, failing if the reader ends first
+fn skip_bytes<R: Read>(reader: &mut R, len: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.take(len), &mut io::sink())?;
+    if skipped < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "snapshot ends inside a script",
+        ));
+    }
+    Ok(())
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amount_compression_round_trips() {
+        for amount in &[0, 1, 9, 10, 546, 50_000, 123_456_789, 2_100_000_000_000_000] {
+            assert_eq!(decompress_amount(compress_amount(*amount)), *amount);
+        }
+    }
+
+    #[test]
+    fn snapshot_balances_are_summed_per_key() {
+        let keys: Vec<Point> = (1..4)
+            .map(|i| point_mul(Point::g(), &Field256::from(i)))
+            .collect();
+        let mut p2wpkh = vec![0x00, 0x14];
+        p2wpkh.extend(&hash160(&keys[1].serialize()));
+
+        let coins = vec![
+            // P2PKH to the uncompressed encoding of key 0
+            coin(1000, &[0x00], &hash160(&keys[0].serialize_uncompressed())),
+            // P2PK to key 0
+            coin(2000, &keys[0].serialize()[..1], &keys[0].serialize()[1..]),
+            // P2WPKH to key 1
+            coin(500, &varint(p2wpkh.len() as u64 + SPECIAL_SCRIPTS), &p2wpkh),
+            // P2SH, never matched
+            coin(700, &[0x01], &[0; 20]),
+        ];
+        let snapshot = snapshot(&coins);
+
+        let reader = SnapshotReader::new(&snapshot[..]).unwrap();
+        assert_eq!(reader.header().coins_count, 4);
+        let balances = reader.balances(&keys).unwrap();
+
+        assert_eq!(
+            balances,
+            vec![
+                (keys[0].clone(), BigUint::from(3000u16)),
+                (keys[1].clone(), BigUint::from(500u16)),
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_skips_oversized_scripts() {
+        let key = point_mul(Point::g(), &Field256::from(1));
+        let script = [0; MAX_SCRIPT_SIZE as usize + 1];
+        let coins = vec![
+            coin(100, &varint(script.len() as u64 + SPECIAL_SCRIPTS), &script),
+            coin(200, &key.serialize()[..1], &key.serialize()[1..]),
+        ];
+        let snapshot_bytes = snapshot(&coins);
+        let reader = SnapshotReader::new(&snapshot_bytes[..]).unwrap();
+        assert_eq!(
+            reader.balances(&[key.clone()]).unwrap(),
+            vec![(key.clone(), BigUint::from(200u8))]
+        );
+
+        // A corrupt size is an error rather than a huge allocation
+        let coins = vec![coin(100, &varint(1 << 40), &[0; 10])];
+        let snapshot_bytes = snapshot(&coins);
+        let reader = SnapshotReader::new(&snapshot_bytes[..]).unwrap();
+        assert!(reader.balances(&[key]).is_err());
+    }
+
+    #[test]
+    fn snapshot_rejects_unknown_format() {
+        let mut snapshot = snapshot(&[]);
+        snapshot[5] = 1;
+
+        assert!(SnapshotReader::new(&snapshot[..]).is_err());
+    }
+
+    #[test]
+    fn snapshot_flags_asset_balance_mismatches() {
+        let (g, h) = (crate::g(), crate::h());
+        let sks: Vec<Field256> = (1..4).map(Field256::from).collect();
+        let keys: Vec<Point> = sks.iter().map(|sk| point_mul(g.clone(), sk)).collect();
+        let coins = vec![
+            coin(3000, &keys[0].serialize()[..1], &keys[0].serialize()[1..]),
+            coin(500, &keys[1].serialize()[..1], &keys[1].serialize()[1..]),
+        ];
+        let snapshot = snapshot(&coins);
+        let proofs = vec![
+            AssetProof::create(
+                Some(sks[0].clone()),
+                &keys[0],
+                BigUint::from(3000u16),
+                &g,
+                &h,
+            ),
+            AssetProof::create(None, &keys[1], BigUint::from(999u16), &g, &h),
+            AssetProof::create(None, &keys[2], BigUint::from(10u8), &g, &h),
+        ];
+
+        let reader = SnapshotReader::new(&snapshot[..]).unwrap();
+        let mismatches = reader
+            .verify_asset_balances(&Params::default(), &proofs, &[])
+            .unwrap();
+
+        assert_eq!(
+            mismatches,
+            vec![
+                BalanceMismatch::Incorrect {
+                    y: keys[1].clone(),
+                    snapshot_balance: BigUint::from(500u16),
+                },
+                BalanceMismatch::Missing { y: keys[2].clone() },
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_flags_multisig_balance_mismatches() {
+        let (g, h) = (crate::g(), crate::h());
+        let sks: Vec<Field256> = (1..4).map(Field256::from).collect();
+        let keys: Vec<Point> = sks.iter().map(|sk| point_mul(g.clone(), sk)).collect();
+        let known: Vec<Option<Field256>> = sks.into_iter().map(Some).collect();
+        let script = multisig_script(&keys, 2).unwrap();
+        let mut p2wsh = vec![0x00, 0x20];
+        p2wsh.extend(&sha256(&script));
+        let coins = vec![
+            // Bare 2-of-3 multisig
+            coin(
+                1000,
+                &varint(script.len() as u64 + SPECIAL_SCRIPTS),
+                &script,
+            ),
+            // P2WSH and P2SH-P2WSH of the same script
+            coin(2000, &varint(p2wsh.len() as u64 + SPECIAL_SCRIPTS), &p2wsh),
+            coin(3000, &[0x01], &hash160(&p2wsh)),
+            // P2SH of the 3-of-3 script
+            coin(500, &[0x01], &hash160(&multisig_script(&keys, 3).unwrap())),
+        ];
+        let snapshot = snapshot(&coins);
+        let multisig = |threshold, bal: u16| {
+            MultisigAssetProof::create(&known, &keys, threshold, BigUint::from(bal), &g, &h)
+        };
+        let proofs = vec![multisig(2, 6000), multisig(3, 999), multisig(1, 10)];
+
+        let reader = SnapshotReader::new(&snapshot[..]).unwrap();
+        let mismatches = reader
+            .verify_asset_balances(&Params::default(), &[], &proofs)
+            .unwrap();
+
+        assert_eq!(
+            mismatches,
+            vec![
+                BalanceMismatch::MultisigIncorrect {
+                    keys: keys.clone(),
+                    threshold: 3,
+                    snapshot_balance: BigUint::from(500u16),
+                },
+                BalanceMismatch::MultisigMissing { keys, threshold: 1 },
+            ]
+        );
+    }
+
+    fn snapshot(coins: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = SNAPSHOT_MAGIC.to_vec();
+        out.extend(&SNAPSHOT_VERSION.to_le_bytes());
+        out.extend(&[0xf9, 0xbe, 0xb4, 0xd9]);
+        out.extend(&[0; 32]);
+        out.extend(&(coins.len() as u64).to_le_bytes());
+
+        if !coins.is_empty() {
+            out.extend(&[1; 32]);
+            out.push(coins.len() as u8);
+            for (vout, coin) in coins.iter().enumerate() {
+                out.push(vout as u8);
+                out.extend(coin);
+            }
+        }
+
+        out
+    }
+
+    fn coin(amount: u64, script_prefix: &[u8], script: &[u8]) -> Vec<u8> {
+        // Height 100, not coinbase
+        let mut out = varint(200);
+        out.extend(varint(compress_amount(amount)));
+        out.extend(script_prefix);
+        out.extend(script);
+        out
+    }
+
+    fn compress_amount(mut n: u64) -> u64 {
+        if n == 0 {
+            return 0;
+        }
+
+        let mut e = 0;
+        while n % 10 == 0 && e < 9 {
+            n /= 10;
+            e += 1;
+        }
+
+        if e < 9 {
+            let d = n % 10;
+            n /= 10;
+            1 + (n * 9 + d - 1) * 10 + e
+        } else {
+            1 + (n - 1) * 10 + 9
+        }
+    }
+
+    fn varint(mut n: u64) -> Vec<u8> {
+        let mut out = vec![(n & 0x7f) as u8];
+        while n > 0x7f {
+            n = (n >> 7) - 1;
+            out.push((n & 0x7f) as u8 | 0x80);
+        }
+        out.reverse();
+        out
+    }
+}
@@ -0,0 +1,321 @@
+use crate::data_source::{Proof, ProofSource};
+use crate::params::Params;
+use crate::proofs::{ensure, Location, SolvencyBundle, VerificationError, VerificationReport};
+use crate::secp256k1::{point_add, Point};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Number of proofs read from the source and checked together by default
+const DEFAULT_BATCH_SIZE: usize = 1024;
+/// Number of failed proof checks kept in the report by default
+const DEFAULT_MAX_FAILURES: usize = 1000;
+
+/// Verifies a bundle against the proofs in a proof store without loading them all into memory.
+///
+/// Proofs are read in batches of `batch_size` and checked in parallel, keeping only running sums
+/// of the asset and liability commitments and at most `max_failures` failed checks. Once the source is exhausted the proof of solvency is
+/// checked against the sums and the liability sum against the bundle's commitment root. The roots
+/// themselves aren't recomputed since that needs every liability proof at once, see
+/// `SolvencyBundle::check`.
+pub struct StreamingVerifier<'a> {
+    bundle: &'a SolvencyBundle,
+    params: Params,
+    batch_size: usize,
+    max_failures: usize,
+}
+
+impl<'a> StreamingVerifier<'a> {
+    pub fn new(bundle: &'a SolvencyBundle) -> StreamingVerifier<'a> {
+        StreamingVerifier {
+            bundle,
+            params: Params::default(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            max_failures: DEFAULT_MAX_FAILURES,
+        }
+    }
+
+    /// Parameters the bundle and every proof are expected to be over, the default ones unless
+    /// set
+    pub fn params(mut self, params: Params) -> StreamingVerifier<'a> {
+        self.params = params;
+        self
+    }
+
+    /// Number of proofs held in memory and checked together
+    pub fn batch_size(mut self, batch_size: usize) -> StreamingVerifier<'a> {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Number of failed proof checks after which further ones are only counted. The checks of
+    /// the bundle itself are always reported.
+    pub fn max_failures(mut self, max_failures: usize) -> StreamingVerifier<'a> {
+        self.max_failures = max_failures;
+        self
+    }
+
+    /// Read and check every proof in `source`, then the bundle, reporting each failed check.
+    /// Fails only if the source can't be read.
+    pub fn verify(&self, source: &mut dyn ProofSource) -> Result<VerificationReport, &'static str> {
+        let mut report = VerificationReport::default();
+        let mut counts = Counts::default();
+        let mut totals = Sums::default();
+        report.record(ensure(
+            self.bundle.params.matches(&self.params),
+            "bundle parameters are the expected ones",
+        ));
+
+        loop {
+            let mut batch = Vec::with_capacity(self.batch_size);
+            while batch.len() < self.batch_size {
+                match source.next_proof()? {
+                    None => break,
+                    Some(proof) => batch.push((counts.add(&proof), proof)),
+                }
+            }
+            if batch.is_empty() {
+                break;
+            }
+
+            let checked = check_batch(&batch, &self.params);
+            for result in checked.results {
+                report.record_up_to(result, self.max_failures);
+            }
+            totals = totals.merge(checked.sums);
+        }
+
+        let solvency = &self.bundle.solvency;
+        let totals_match = match (&totals.assets, &totals.liabilities) {
+            (Some(assets), Some(liabilities)) => ensure(
+                solvency.verify_totals(assets, liabilities),
+                "proven commitment is the asset commitments less the liability commitment",
+            ),
+            _ => Err(VerificationError::new(
+                "proof store holds asset and liability proofs",
+            )),
+        };
+        report.record(
+            solvency
+                .check_with(&self.params)
+                .and(totals_match)
+                .map_err(|e| e.at(Location::Solvency)),
+        );
+        report.record(ensure(
+            totals.liabilities.as_ref() == Some(&self.bundle.commitment_root.commitment),
+            "liability commitments sum to the commitment root",
+        ));
+
+        Ok(report)
+    }
+}
+
+/// Running counts of the proofs read so far
+#[derive(Default)]
+struct Counts {
+    assets: usize,
+    multisigs: usize,
+    liabilities: usize,
+}
+
+impl Counts {
+    /// Count the proof, returning its location among the proofs of its kind
+    fn add(&mut self, proof: &Proof) -> Location {
+        match proof {
+            Proof::Asset(_) => {
+                self.assets += 1;
+                Location::Asset(self.assets - 1)
+            }
+            Proof::Multisig(_) => {
+                self.multisigs += 1;
+                Location::MultisigAsset(self.multisigs - 1)
+            }
+            Proof::Liability(_) => {
+                self.liabilities += 1;
+                Location::Liability(self.liabilities - 1)
+            }
+        }
+    }
+}
+
+/// Sums of the asset and liability commitments of a set of proofs
+#[derive(Default)]
+struct Sums {
+    assets: Option<Point>,
+    liabilities: Option<Point>,
+}
+
+impl Sums {
+    fn add(mut self, proof: &Proof) -> Sums {
+        match proof {
+            Proof::Asset(proof) => accumulate(&mut self.assets, proof.p_ref().clone()),
+            Proof::Multisig(proof) => accumulate(&mut self.assets, proof.p_ref().clone()),
+            Proof::Liability(proof) => accumulate(&mut self.liabilities, proof.z()),
+        }
+        self
+    }
+
+    fn merge(mut self, other: Sums) -> Sums {
+        if let Some(assets) = other.assets {
+            accumulate(&mut self.assets, assets);
+        }
+        if let Some(liabilities) = other.liabilities {
+            accumulate(&mut self.liabilities, liabilities);
+        }
+        self
+    }
+}
+
+fn accumulate(sum: &mut Option<Point>, point: Point) {
+    *sum = Some(match sum.take() {
+        None => point,
+        Some(sum) => point_add(sum, &point),
+    });
+}
+
+/// Results of checking a batch of proofs, in order, and the sums of their commitments
+#[derive(Default)]
+struct Checked {
+    results: Vec<Result<(), VerificationError>>,
+    sums: Sums,
+}
+
+impl Checked {
+    fn add(mut self, proof: &(Location, Proof), params: &Params) -> Checked {
+        self.results.push(check_proof(proof, params));
+        self.sums = self.sums.add(&proof.1);
+        self
+    }
+
+    fn merge(mut self, other: Checked) -> Checked {
+        self.results.extend(other.results);
+        self.sums = self.sums.merge(other.sums);
+        self
+    }
+}
+
+fn check_proof(proof: &(Location, Proof), params: &Params) -> Result<(), VerificationError> {
+    let (location, proof) = proof;
+    match proof {
+        Proof::Asset(proof) => proof.check_with(params),
+        Proof::Multisig(proof) => proof.check_with(params),
+        Proof::Liability(proof) => proof.check_with(params),
+    }
+    .map_err(|e| e.at(*location))
+}
+
+/// Check the batch and sum its commitments, each worker summing the proofs it checked
+#[cfg(feature = "rayon")]
+fn check_batch(batch: &[(Location, Proof)], params: &Params) -> Checked {
+    batch
+        .par_iter()
+        .fold(Checked::default, |checked, proof| {
+            checked.add(proof, params)
+        })
+        .reduce(Checked::default, Checked::merge)
+}
+
+/// Check the batch on the calling thread, for builds without rayon
+#[cfg(not(feature = "rayon"))]
+fn check_batch(batch: &[(Location, Proof)], params: &Params) -> Checked {
+    batch.iter().fold(Checked::default(), |checked, proof| {
+        checked.add(proof, params)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_source::MemoryProofStore;
+    use crate::epoch::Epoch;
+    use crate::fields::Field256;
+    use crate::proofs::{AssetProof, LiabilityProof};
+    use crate::secp256k1::point_mul;
+    use num_bigint::BigUint;
+
+    fn store_and_bundle() -> (MemoryProofStore, SolvencyBundle) {
+        let (g, h) = (crate::g(), crate::h());
+        let mut store = MemoryProofStore::new();
+        for i in 1..4 {
+            let x = Field256::from(i);
+            let y = point_mul(Point::g(), &x);
+            let proof = AssetProof::create(Some(x), &y, BigUint::from(10u8), &g, &h);
+            store.assets.push(proof);
+        }
+        for i in 0..5u8 {
+            let proof = LiabilityProof::create(&[i], &BigUint::from(6u8), g.clone(), h.clone());
+            store.liabilities.push(proof);
+        }
+
+        let bundle = SolvencyBundle::create(
+            Epoch::new(1, 1_560_000_000, 580_000),
+            &store.assets,
+            &[],
+            &store.liabilities,
+            Params::default(),
+        )
+        .unwrap();
+        (store, bundle)
+    }
+
+    #[test]
+    fn streaming_verifier_accepts_matching_store() {
+        let (mut store, bundle) = store_and_bundle();
+
+        let report = StreamingVerifier::new(&bundle)
+            .batch_size(2)
+            .verify(&mut store)
+            .unwrap();
+
+        assert!(report.is_ok(), "{}", report);
+    }
+
+    #[test]
+    fn streaming_verifier_reports_missing_liability() {
+        let (mut store, bundle) = store_and_bundle();
+        store.liabilities.pop();
+
+        let report = StreamingVerifier::new(&bundle)
+            .batch_size(2)
+            .verify(&mut store)
+            .unwrap();
+
+        assert_eq!(report.failures.len(), 2);
+        assert_eq!(report.failures[0].location, vec![Location::Solvency]);
+    }
+
+    #[test]
+    fn streaming_verifier_rejects_unexpected_params() {
+        let (mut store, bundle) = store_and_bundle();
+
+        let report = StreamingVerifier::new(&bundle)
+            .params(Params::new(b"test"))
+            .verify(&mut store)
+            .unwrap();
+
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.failures[0],
+            VerificationError::new("bundle parameters are the expected ones")
+        );
+    }
+
+    #[test]
+    fn streaming_verifier_bounds_reported_failures() {
+        let (mut store, bundle) = store_and_bundle();
+
+        // Every proof is over other generators than expected
+        let report = StreamingVerifier::new(&bundle)
+            .params(Params::new(b"test"))
+            .max_failures(3)
+            .verify(&mut store)
+            .unwrap();
+
+        // The parameters and two proofs, then the solvency proof, with the other six proofs
+        // only counted
+        assert_eq!(report.failures.len(), 4);
+        assert_eq!(report.omitted, 6);
+        assert_eq!(report.failures[3].location, vec![Location::Solvency]);
+        assert!(report.to_string().starts_with("10 checks failed"));
+    }
+}
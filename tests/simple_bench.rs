@@ -1,5 +1,9 @@
+use num_bigint::BigUint;
 use provisions::builders::AssetProofBuilder;
 use provisions::data_source::asset::Rocks;
+use provisions::fields::Field256;
+use provisions::secp256k1::{point_mul, Point};
+use provisions::signer::MemorySigner;
 use rocksdb::{Options, DB};
 use std::time::Instant;
 
@@ -10,10 +14,15 @@ fn simple_rocks_bench() {
     {
         let now = Instant::now();
         let asset_count = 10;
-        let mut asset_ds = Rocks::new(asset_count, path);
-        let mut builder = AssetProofBuilder::new(&mut asset_ds);
+        let xs: Vec<Field256> = (1..=asset_count).map(Field256::from).collect();
+        let mut assets = xs
+            .iter()
+            .map(|x| (point_mul(Point::g(), x), BigUint::from(10u8)));
+        let mut store = Rocks::open(path).expect("open");
+        let signer = MemorySigner::new(xs.clone());
+        let mut builder = AssetProofBuilder::new(&mut assets, &mut store, &signer);
 
-        builder.build();
+        builder.build().expect("build");
 
         // Remember to use -- --nocapture option when running to see this
         println!("Took: {}s", now.elapsed().as_secs());